@@ -0,0 +1,151 @@
+//! Derive macros for rendy3d
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, Index, Member, Type, parse_macro_input, parse_quote};
+
+/// Implements `Interpolate` for a struct by interpolating each of its fields
+///
+/// Fields are interpolated perspective-correctly by default. They can be changed with an attribute:
+/// - `#[interpolate(flat)]` takes the value from the first vertex, like `Static`, and only needs the field to be `Clone`
+/// - `#[interpolate(no_perspective)]` interpolates linearly in screen space, like `NoPerspective`
+/// - `#[interpolate(perspective)]` is the default
+///
+/// The generated code refers to `::rendy3d`, so it works with or without the standard library
+#[proc_macro_derive(Interpolate, attributes(interpolate))]
+pub fn derive_interpolate(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+	match interpolate(input) {
+		Ok(tokens) => tokens.into(),
+		Err(error) => error.to_compile_error().into(),
+	}
+}
+
+/// How a field is interpolated
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+	Perspective,
+	NoPerspective,
+	Flat,
+}
+
+struct Field {
+	member: Member,
+	ty: Type,
+	mode: Mode,
+}
+
+fn interpolate(input: DeriveInput) -> syn::Result<TokenStream2> {
+	let Data::Struct(data) = &input.data else {
+		return Err(syn::Error::new_spanned(
+			&input.ident,
+			"Interpolate can only be derived for structs",
+		));
+	};
+	let fields = match &data.fields {
+		Fields::Named(fields) => fields.named.iter().collect(),
+		Fields::Unnamed(fields) => fields.unnamed.iter().collect(),
+		Fields::Unit => Vec::new(),
+	}
+	.into_iter()
+	.enumerate()
+	.map(|(i, field)| {
+		let member = match &field.ident {
+			Some(ident) => Member::Named(ident.clone()),
+			None => Member::Unnamed(Index::from(i)),
+		};
+		Ok(Field {
+			member,
+			ty: field.ty.clone(),
+			mode: mode(field)?,
+		})
+	})
+	.collect::<syn::Result<Vec<_>>>()?;
+
+	let krate = quote!(::rendy3d::graphics::interpolate);
+	let name = &input.ident;
+	let mut generics = input.generics.clone();
+	let where_clause = generics.make_where_clause();
+	for field in &fields {
+		let ty = &field.ty;
+		where_clause.predicates.push(if field.mode == Mode::Flat {
+			parse_quote!(#ty: ::core::clone::Clone)
+		} else {
+			parse_quote!(#ty: #krate::Interpolate)
+		});
+	}
+	let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+	// Builds `Self` from an expression for each interpolated field
+	let construct = |interpolated: &dyn Fn(&Member, &Type, Mode) -> TokenStream2| {
+		let fields = fields.iter().map(|field| {
+			let member = &field.member;
+			let value = if field.mode == Mode::Flat {
+				quote!(::core::clone::Clone::clone(&a.#member))
+			} else {
+				interpolated(member, &field.ty, field.mode)
+			};
+			quote!(#member: #value)
+		});
+		quote!(Self { #(#fields,)* })
+	};
+	let interpolate3 = construct(
+		&|member, ty, _| quote!(<#ty as #krate::Interpolate>::interpolate3(&a.#member, &b.#member, &c.#member, x, y, z)),
+	);
+	let interpolate2 = construct(
+		&|member, ty, _| quote!(<#ty as #krate::Interpolate>::interpolate2(&a.#member, &b.#member, x, y)),
+	);
+	let interpolate_screen = construct(&|member, ty, mode| {
+		let weights = if mode == Mode::NoPerspective {
+			quote!(&#krate::Weights::linear(weights.screen))
+		} else {
+			quote!(weights)
+		};
+		quote!(<#ty as #krate::Interpolate>::interpolate_screen(&a.#member, &b.#member, &c.#member, #weights))
+	});
+	let difference = construct(
+		&|member, ty, _| quote!(<#ty as #krate::Interpolate>::difference(&a.#member, &b.#member)),
+	);
+
+	Ok(quote! {
+		#[automatically_derived]
+		#[allow(unused_variables)]
+		impl #impl_generics #krate::Interpolate for #name #ty_generics #where_clause {
+			fn interpolate3(a: &Self, b: &Self, c: &Self, x: f32, y: f32, z: f32) -> Self {
+				#interpolate3
+			}
+			fn interpolate2(a: &Self, b: &Self, x: f32, y: f32) -> Self {
+				#interpolate2
+			}
+			fn interpolate_screen(a: &Self, b: &Self, c: &Self, weights: &#krate::Weights) -> Self {
+				#interpolate_screen
+			}
+			fn difference(a: &Self, b: &Self) -> Self {
+				#difference
+			}
+		}
+	})
+}
+
+/// Reads how a field is interpolated from its `#[interpolate(...)]` attribute
+fn mode(field: &syn::Field) -> syn::Result<Mode> {
+	let mut mode = Mode::Perspective;
+	for attribute in &field.attrs {
+		if !attribute.path().is_ident("interpolate") {
+			continue;
+		}
+		attribute.parse_nested_meta(|meta| {
+			if meta.path.is_ident("flat") {
+				mode = Mode::Flat;
+			} else if meta.path.is_ident("no_perspective") {
+				mode = Mode::NoPerspective;
+			} else if meta.path.is_ident("perspective") {
+				mode = Mode::Perspective;
+			} else {
+				return Err(meta.error("expected `flat`, `no_perspective` or `perspective`"));
+			}
+			Ok(())
+		})?;
+	}
+	Ok(mode)
+}
@@ -46,14 +46,13 @@ pub fn load_obj_indexed<P: AsRef<Path>>(
 ) -> Result<IndexedMesh<TexturedVertex>, Box<dyn std::error::Error>> {
 	let input = BufReader::new(File::open(path)?);
 	let dome: Obj<TexturedVertex_OBJ, u16> = load_obj_1(input)?;
-	let mesh: IndexedMesh<TexturedVertex> = IndexedMesh {
-		vertices: dome
-			.vertices
+	let mesh: IndexedMesh<TexturedVertex> = IndexedMesh::new(
+		dome.vertices
 			.into_iter()
 			.map(convert_textured_vertex)
 			.collect(),
-		indices: dome.indices,
-	};
+		dome.indices,
+	);
 
 	Ok(mesh)
 }
@@ -15,11 +15,11 @@ pub fn load_stl_indexed<P: AsRef<Path>>(path: P) -> IndexedMesh<Vertex, usize> {
 		.map(|v| Vertex::new(Point::new(v.0[0] as f64, v.0[1] as f64, v.0[2] as f64)))
 		.collect::<Vec<Vertex>>();
 	let triangles = stl.faces;
-	IndexedMesh {
+	IndexedMesh::new(
 		vertices,
-		indices: triangles
+		triangles
 			.iter()
 			.flat_map(|t| t.vertices)
 			.collect::<Vec<usize>>(),
-	}
+	)
 }
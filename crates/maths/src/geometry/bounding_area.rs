@@ -1,4 +1,4 @@
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct BoundingArea2D {
 	pub min_x: usize,
 	pub max_x: usize,
@@ -20,4 +20,18 @@ impl BoundingArea2D {
 	pub fn height(&self) -> usize {
 		self.max_y - self.min_y
 	}
+	/// Returns `true` if the area doesn't contain any points
+	pub fn is_empty(&self) -> bool {
+		self.min_x >= self.max_x || self.min_y >= self.max_y
+	}
+	/// Returns the area covered by both `self` and `other`, or `None` if they don't overlap
+	pub fn intersection(&self, other: &Self) -> Option<Self> {
+		let area = Self::new(
+			self.min_x.max(other.min_x),
+			self.max_x.min(other.max_x),
+			self.min_y.max(other.min_y),
+			self.max_y.min(other.max_y),
+		);
+		if area.is_empty() { None } else { Some(area) }
+	}
 }
@@ -14,7 +14,7 @@ impl<Vertex> Triangle<Vertex> {
 			vertex3,
 		}
 	}
-	pub fn map_vertices<T, U: Fn(Vertex) -> T>(self, map_fn: U) -> Triangle<T> {
+	pub fn map_vertices<T, U: FnMut(Vertex) -> T>(self, mut map_fn: U) -> Triangle<T> {
 		Triangle::new(
 			map_fn(self.vertex1),
 			map_fn(self.vertex2),
@@ -1,7 +1,7 @@
 use core::ops::{Mul, MulAssign, Neg};
 
 use crate::{
-	matrices::matrix3::Matrix3,
+	matrices::matrix3::{Matrix3, MatrixInversionError},
 	traits::{float::Float, num::Num, signed::Signed},
 	vector::{vector3::Vector3, vector4::Vector4},
 };
@@ -170,6 +170,60 @@ where
 		Vector3::new(self.w.x, self.w.y, self.w.z)
 	}
 
+	/// Computes the inverse of this matrix, which reverses any transformation it represents
+	///
+	/// For rotations and translations, [`Matrix4::reverse_rotation_translation`] is cheaper
+	pub fn invert(&self) -> Result<Self, MatrixInversionError> {
+		let [a, b, c, d] = [&self.x, &self.y, &self.z, &self.w].map(|v| [v.x, v.y, v.z, v.w]);
+		// Determinants of the 2x2 sub-matrices of the first two and last two columns, which are shared by the cofactors
+		let s = [
+			a[0] * b[1] - b[0] * a[1],
+			a[0] * b[2] - b[0] * a[2],
+			a[0] * b[3] - b[0] * a[3],
+			a[1] * b[2] - b[1] * a[2],
+			a[1] * b[3] - b[1] * a[3],
+			a[2] * b[3] - b[2] * a[3],
+		];
+		let t = [
+			c[0] * d[1] - d[0] * c[1],
+			c[0] * d[2] - d[0] * c[2],
+			c[0] * d[3] - d[0] * c[3],
+			c[1] * d[2] - d[1] * c[2],
+			c[1] * d[3] - d[1] * c[3],
+			c[2] * d[3] - d[2] * c[3],
+		];
+		let det = s[0] * t[5] - s[1] * t[4] + s[2] * t[3] + s[3] * t[2] - s[4] * t[1] + s[5] * t[0];
+		if det == T::zero() {
+			return Err(MatrixInversionError::SingularMatrix);
+		}
+		let inv = T::one() / det;
+		Ok(Self::new(
+			Vector4::new(
+				(b[1] * t[5] - b[2] * t[4] + b[3] * t[3]) * inv,
+				(-a[1] * t[5] + a[2] * t[4] - a[3] * t[3]) * inv,
+				(d[1] * s[5] - d[2] * s[4] + d[3] * s[3]) * inv,
+				(-c[1] * s[5] + c[2] * s[4] - c[3] * s[3]) * inv,
+			),
+			Vector4::new(
+				(-b[0] * t[5] + b[2] * t[2] - b[3] * t[1]) * inv,
+				(a[0] * t[5] - a[2] * t[2] + a[3] * t[1]) * inv,
+				(-d[0] * s[5] + d[2] * s[2] - d[3] * s[1]) * inv,
+				(c[0] * s[5] - c[2] * s[2] + c[3] * s[1]) * inv,
+			),
+			Vector4::new(
+				(b[0] * t[4] - b[1] * t[2] + b[3] * t[0]) * inv,
+				(-a[0] * t[4] + a[1] * t[2] - a[3] * t[0]) * inv,
+				(d[0] * s[4] - d[1] * s[2] + d[3] * s[0]) * inv,
+				(-c[0] * s[4] + c[1] * s[2] - c[3] * s[0]) * inv,
+			),
+			Vector4::new(
+				(-b[0] * t[3] + b[1] * t[1] - b[2] * t[0]) * inv,
+				(a[0] * t[3] - a[1] * t[1] + a[2] * t[0]) * inv,
+				(-d[0] * s[3] + d[1] * s[1] - d[2] * s[0]) * inv,
+				(c[0] * s[3] - c[1] * s[1] + c[2] * s[0]) * inv,
+			),
+		))
+	}
 	/// Computes a matrix that reverses the combination of translation and rotation represented by this Matrix
 	pub fn reverse_rotation_translation(&self) -> Self {
 		// A combined rotation-translation matrix applies the rotation first, then the translation
@@ -230,4 +284,26 @@ mod test {
 		);
 		assert_eq!(mat.determinant(), 0);
 	}
+
+	#[test]
+	fn invert() {
+		let mat = Matrix4::new_perspective(1.0, 1.2, 20.0, 0.1)
+			* Matrix4::translation(Vector3::new(1.0, -2.0, 3.0))
+			* Matrix4::rotation_y(0.5);
+		let product = mat.invert().unwrap() * mat;
+		// Every column of the identity matrix is recovered
+		for column in [
+			(1.0, 0.0, 0.0, 0.0),
+			(0.0, 1.0, 0.0, 0.0),
+			(0.0, 0.0, 1.0, 0.0),
+			(0.0, 0.0, 0.0, 1.0),
+		] {
+			let (x, y, z, w) = column;
+			let (a, b, c, d) = (product.clone() * Vector4::new(x, y, z, w)).as_tuple();
+			for (actual, expected) in [(a, x), (b, y), (c, z), (d, w)] {
+				assert!((actual - expected).abs() < 1e-9, "{product:?}");
+			}
+		}
+		assert!(Matrix4::<f64>::default().invert().is_err());
+	}
 }
@@ -1,6 +1,8 @@
 use crate::traits::{num::Num, signed::Signed};
 pub trait Float: Num + Signed {
 	fn sqrt(self) -> Self;
+	fn powf(self, n: Self) -> Self;
+	fn exp(self) -> Self;
 	// Trig
 	fn sin(self) -> Self;
 	fn cos(self) -> Self;
@@ -16,6 +18,12 @@ macro_rules! implement_float {
 			fn sqrt(self) -> Self {
 				Self::sqrt(self)
 			}
+			fn powf(self, n: Self) -> Self {
+				Self::powf(self, n)
+			}
+			fn exp(self) -> Self {
+				Self::exp(self)
+			}
 			fn sin(self) -> Self {
 				Self::sin(self)
 			}
@@ -45,6 +53,12 @@ macro_rules! implement_float {
 			fn sqrt(self) -> Self {
 				<Self as F32Ext>::sqrt(self)
 			}
+			fn powf(self, n: Self) -> Self {
+				<Self as F32Ext>::powf(self, n)
+			}
+			fn exp(self) -> Self {
+				<Self as F32Ext>::exp(self)
+			}
 			fn sin(self) -> Self {
 				<Self as F32Ext>::sin(self)
 			}
@@ -75,6 +89,12 @@ impl Float for f64 {
 	fn sqrt(self) -> Self {
 		F32Ext::sqrt(self as f32) as f64
 	}
+	fn powf(self, n: Self) -> Self {
+		F32Ext::powf(self as f32, n as f32) as f64
+	}
+	fn exp(self) -> Self {
+		F32Ext::exp(self as f32) as f64
+	}
 	// Trig
 	fn sin(self) -> Self {
 		F32Ext::sin(self as f32) as f64
@@ -2,13 +2,21 @@ use criterion::{BatchSize::SmallInput, Criterion, criterion_group, criterion_mai
 use rendy3d::{
 	graphics::{
 		colour::Colour,
-		geometry::{clipping::SutherlandHodgman, point::AbsoluteScreenCoordinate},
+		geometry::{
+			clipping::SutherlandHodgman,
+			point::{AbsoluteScreenCoordinate, ScreenCoordinate},
+		},
 		geometry_3d::{cube::Cube, point::Point},
 		pipeline::Pipeline,
+		rasterizer::{
+			Rasterizer, edge_function_rasterizer::EdgeFunctionRasterizer,
+			triangle_rasterizer::TriangleRasterizer,
+		},
 		screen::Screen,
 	},
 	render::render,
 };
+use rendy3d_maths::geometry::triangle::Triangle;
 use rendy3d_maths::{matrices::matrix4::Matrix4, vector::vector3::Vector3};
 use std::hint::black_box;
 struct Bench {
@@ -77,5 +85,50 @@ fn criterion_benchmark(c: &mut Criterion) {
 	});
 }
 
-criterion_group!(benches, criterion_benchmark);
+fn rasterizer_benchmark(c: &mut Criterion) {
+	let width = 640;
+	let height = 480;
+	// Large triangle covering roughly half of the screen
+	let triangle = Triangle::new(
+		(ScreenCoordinate::new(20.5, 10.25, 0.5), Colour::RED),
+		(ScreenCoordinate::new(60.25, 470.0, 0.5), Colour::BLUE),
+		(ScreenCoordinate::new(600.75, 240.5, 0.5), Colour::GREEN),
+	);
+	let mut group = c.benchmark_group("rasterize triangle");
+	let setup = || {
+		(
+			vec![Colour::BLACK; width * height],
+			vec![f32::INFINITY; width * height],
+		)
+	};
+	group.bench_function("barycentric", |b| {
+		b.iter_batched_ref(
+			setup,
+			|(display_buffer, z_buffer)| {
+				TriangleRasterizer::draw(
+					&mut Screen::new(display_buffer, z_buffer, width, height),
+					&Bench { c: Colour::RED },
+					black_box(triangle.clone()),
+				)
+			},
+			SmallInput,
+		)
+	});
+	group.bench_function("edge function", |b| {
+		b.iter_batched_ref(
+			setup,
+			|(display_buffer, z_buffer)| {
+				EdgeFunctionRasterizer::draw(
+					&mut Screen::new(display_buffer, z_buffer, width, height),
+					&Bench { c: Colour::RED },
+					black_box(triangle.clone()),
+				)
+			},
+			SmallInput,
+		)
+	});
+	group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark, rasterizer_benchmark);
 criterion_main!(benches);
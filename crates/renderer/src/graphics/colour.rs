@@ -5,7 +5,7 @@ use derive_more::Add;
 use crate::graphics::interpolate::Interpolate;
 
 #[repr(C)]
-#[derive(Clone, Debug, Copy, Add)]
+#[derive(Clone, Debug, Copy, PartialEq, Add)]
 pub struct Colour {
 	pub red: u8,
 	pub green: u8,
@@ -58,6 +58,15 @@ impl Interpolate for Colour {
 	fn interpolate2(a: &Self, b: &Self, x: f32, y: f32) -> Self {
 		*a * x + *b * y
 	}
+	/// Channels can't be negative, so this is the absolute difference of each channel
+	fn difference(a: &Self, b: &Self) -> Self {
+		Colour::new(
+			a.red.abs_diff(b.red),
+			a.green.abs_diff(b.green),
+			a.blue.abs_diff(b.blue),
+			a.alpha.abs_diff(b.alpha),
+		)
+	}
 }
 
 impl Mul<f64> for Colour {
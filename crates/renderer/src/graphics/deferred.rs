@@ -0,0 +1,331 @@
+//! Deferred shading
+//!
+//! Rendering is split into two passes. The geometry pass draws the scene into a [`GBuffer`], using any [`Pipeline`](crate::graphics::pipeline::Pipeline)
+//! whose fragments are [`GBufferFragment`]s, which records the nearest surface in each pixel.
+//! The lighting pass then runs once for each pixel that is covered by geometry, so its cost doesn't depend on how much overdraw there was.
+//! [`PointLights`] shades surfaces with any number of point lights
+use rendy3d_maths::{geometry::bounding_area::BoundingArea2D, vector::vector3::Vector3};
+
+#[cfg(feature = "std")]
+use crate::graphics::target::SplitTarget;
+use crate::graphics::{colour::Colour, framebuffer::Framebuffer, target::Target};
+
+/// Fragment written to a [`GBuffer`] by the geometry pass
+///
+/// Holds the albedo, normal, position and material of the surface, in that order
+pub type GBufferFragment = (Colour, Vector3<f32>, Vector3<f32>, Material);
+
+/// Attachments of a [`GBuffer`], which are written to by the geometry pass
+pub type GBufferAttachments<'a> = (
+	&'a mut [Colour],
+	&'a mut [Vector3<f32>],
+	&'a mut [Vector3<f32>],
+	&'a mut [Material],
+);
+
+/// Parameters of a surface that affect how it is lit
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Material {
+	/// Strength of specular highlights
+	pub specular: f32,
+	/// Specular exponent. Higher values give smaller, sharper highlights
+	pub shininess: u32,
+}
+impl Material {
+	pub const fn new(specular: f32, shininess: u32) -> Self {
+		Self {
+			specular,
+			shininess,
+		}
+	}
+}
+
+/// Surface that is visible in a pixel of a [`GBuffer`]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Surface {
+	pub albedo: Colour,
+	pub normal: Vector3<f32>,
+	pub position: Vector3<f32>,
+	pub depth: f32,
+	pub material: Material,
+}
+
+/// Buffers holding the surface that is visible in each pixel
+///
+/// Every buffer must hold `width * height` values
+pub struct GBuffer<'a> {
+	pub albedo: &'a mut [Colour],
+	pub normal: &'a mut [Vector3<f32>],
+	pub position: &'a mut [Vector3<f32>],
+	pub material: &'a mut [Material],
+	pub z_buffer: &'a mut [f32],
+	width: usize,
+	height: usize,
+}
+impl<'a> GBuffer<'a> {
+	pub fn new(
+		albedo: &'a mut [Colour],
+		normal: &'a mut [Vector3<f32>],
+		position: &'a mut [Vector3<f32>],
+		material: &'a mut [Material],
+		z_buffer: &'a mut [f32],
+		width: usize,
+		height: usize,
+	) -> Self {
+		Self {
+			albedo,
+			normal,
+			position,
+			material,
+			z_buffer,
+			width,
+			height,
+		}
+	}
+	/// Target for the geometry pass
+	pub fn target(&mut self) -> Framebuffer<'_, GBufferAttachments<'_>> {
+		Framebuffer::new(
+			(
+				&mut *self.albedo,
+				&mut *self.normal,
+				&mut *self.position,
+				&mut *self.material,
+			),
+			self.z_buffer,
+			self.width,
+			self.height,
+		)
+	}
+	/// Removes every surface from the buffer
+	pub fn clear(&mut self) {
+		self.target().clear(GBufferFragment::default());
+	}
+	pub fn area(&self) -> BoundingArea2D {
+		BoundingArea2D::new(0, self.width, 0, self.height)
+	}
+	/// Surface that is visible in a pixel, or `None` if nothing was drawn there
+	pub fn surface(&self, x: usize, y: usize) -> Option<Surface> {
+		let i = y * self.width + x;
+		let depth = self.z_buffer[i];
+		depth.is_finite().then(|| Surface {
+			albedo: self.albedo[i],
+			normal: self.normal[i],
+			position: self.position[i],
+			depth,
+			material: self.material[i],
+		})
+	}
+	/// Lighting pass, which shades every pixel covered by geometry and writes it to `target`
+	///
+	/// The depth of each surface is also written, so that more geometry can be drawn over the result (e.g. transparent objects).
+	/// Pixels that aren't covered by geometry are left untouched
+	pub fn light<T: Target<Item = Colour>, L: Lighting>(&self, lighting: &L, target: &mut T) {
+		let Some(area) = self.area().intersection(&target.area()) else {
+			return;
+		};
+		for y in area.min_y..area.max_y {
+			for x in area.min_x..area.max_x {
+				if let Some(surface) = self.surface(x, y) {
+					target.set(x, y, lighting.shade(&surface));
+					target.set_depth(x, y, surface.depth);
+				}
+			}
+		}
+	}
+	/// Multithreaded version of [`GBuffer::light`]
+	#[cfg(feature = "std")]
+	pub fn light_parallel<T, L>(&self, lighting: &L, target: &mut T)
+	where
+		T: SplitTarget<Item = Colour>,
+		L: Lighting + Sync,
+	{
+		use std::{num::NonZero, thread};
+
+		let workers = thread::available_parallelism().map_or(1, NonZero::get);
+		let rows = self.height.div_ceil(workers).max(1);
+		let tiles = target.split(&self.area(), rows);
+		thread::scope(|scope| {
+			for mut tile in tiles {
+				scope.spawn(move || self.light(lighting, &mut tile));
+			}
+		});
+	}
+}
+
+/// Lighting pass of a deferred renderer
+pub trait Lighting {
+	/// Colour of a surface once it has been lit
+	fn shade(&self, surface: &Surface) -> Colour;
+}
+
+/// Light that shines equally in all directions from a point, and fades out with distance
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PointLight {
+	pub position: Vector3<f32>,
+	pub colour: Colour,
+	pub intensity: f32,
+	/// Distance at which the light has faded out completely. Surfaces further away than this aren't affected by the light
+	pub radius: f32,
+}
+impl PointLight {
+	pub const fn new(position: Vector3<f32>, colour: Colour, intensity: f32, radius: f32) -> Self {
+		Self {
+			position,
+			colour,
+			intensity,
+			radius,
+		}
+	}
+}
+
+/// Blinn-Phong lighting from a set of point lights
+pub struct PointLights<'a> {
+	pub lights: &'a [PointLight],
+	/// Light that reaches every surface, regardless of the point lights
+	pub ambient: f32,
+	/// Position of the camera, which specular highlights depend on
+	pub camera: Vector3<f32>,
+}
+impl Lighting for PointLights<'_> {
+	fn shade(&self, surface: &Surface) -> Colour {
+		let channels = |c: Colour| [c.red, c.green, c.blue].map(|c| c as f32 / 255.0);
+		let normal = surface.normal.normalized();
+		let view = (self.camera - surface.position).normalized();
+		let mut diffuse = [self.ambient; 3];
+		let mut specular = [0.0; 3];
+		for light in self.lights {
+			let to_light = light.position - surface.position;
+			let distance = to_light.magnitude();
+			if distance >= light.radius {
+				continue;
+			}
+			let direction = to_light * (1.0 / distance);
+			let lambert = normal.dot_with(&direction);
+			if lambert <= 0.0 {
+				continue;
+			}
+			let falloff = 1.0 - distance / light.radius;
+			let strength = falloff * falloff * light.intensity;
+			let half = (direction + view).normalized();
+			let highlight = powi(normal.dot_with(&half).max(0.0), surface.material.shininess)
+				* surface.material.specular;
+			for (i, c) in channels(light.colour).into_iter().enumerate() {
+				diffuse[i] += c * lambert * strength;
+				specular[i] += c * highlight * strength;
+			}
+		}
+		let albedo = channels(surface.albedo);
+		let [red, green, blue] = [0, 1, 2]
+			.map(|i| ((albedo[i] * diffuse[i] + specular[i]) * 255.0).clamp(0.0, 255.0) as u8);
+		Colour::new(red, green, blue, surface.albedo.alpha)
+	}
+}
+
+/// Raises `base` to an integer power
+fn powi(mut base: f32, mut exponent: u32) -> f32 {
+	let mut result = 1.0;
+	while exponent > 0 {
+		if exponent & 1 == 1 {
+			result *= base;
+		}
+		base *= base;
+		exponent >>= 1;
+	}
+	result
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+	use rendy3d_maths::geometry::triangle::Triangle;
+
+	use super::*;
+	use crate::{
+		graphics::{
+			geometry::{clipping::NoClip, point::AbsoluteScreenCoordinate},
+			geometry_3d::point::Point,
+			pipeline::Pipeline,
+			screen::Screen,
+		},
+		render::render,
+	};
+
+	/// Writes a white surface facing the camera, using the vertex positions directly as world space positions
+	struct Geometry;
+	impl Pipeline for Geometry {
+		type VsOut = Vector3<f32>;
+		type VsIn = ();
+		type Vertex = Point;
+		type Fragment = GBufferFragment;
+		type ClippingStrategy = NoClip;
+
+		fn vertex(&self, _index: usize, vertex: Point, _state: ()) -> (Point, Vector3<f32>) {
+			let position = Vector3::new(vertex.x as f32, vertex.y as f32, vertex.z as f32);
+			(vertex, position)
+		}
+		fn fragment(
+			&self,
+			_position: AbsoluteScreenCoordinate,
+			position: Vector3<f32>,
+		) -> GBufferFragment {
+			(
+				Colour::WHITE,
+				Vector3::new(0.0, 0.0, -1.0),
+				position,
+				Material::new(0.5, 16),
+			)
+		}
+	}
+
+	#[test]
+	fn covered_pixels_are_lit() {
+		let (width, height) = (32, 32);
+		let pixels = width * height;
+		let mut albedo = vec![Colour::default(); pixels];
+		let mut normal = vec![Vector3::default(); pixels];
+		let mut position = vec![Vector3::default(); pixels];
+		let mut material = vec![Material::default(); pixels];
+		let mut gbuffer_depth = vec![0.0; pixels];
+		let mut gbuffer = GBuffer::new(
+			&mut albedo,
+			&mut normal,
+			&mut position,
+			&mut material,
+			&mut gbuffer_depth,
+			width,
+			height,
+		);
+		gbuffer.clear();
+		// Triangle covering the bottom-left half of the screen
+		let triangle = Triangle::new(
+			Point::new(-1.0, -1.0, 0.5),
+			Point::new(1.0, -1.0, 0.5),
+			Point::new(-1.0, 1.0, 0.5),
+		);
+		render([triangle], &mut Geometry, &mut gbuffer.target(), ());
+
+		let mut frame = vec![Colour::BLACK; pixels];
+		let mut depth = vec![f32::INFINITY; pixels];
+		let mut screen = Screen::new(&mut frame, &mut depth, width, height);
+		// One light in front of the bottom-left corner, and one behind the surface which can't light it
+		let lights = [
+			PointLight::new(Vector3::new(-0.8, -0.8, 0.0), Colour::RED, 4.0, 1.0),
+			PointLight::new(Vector3::new(0.0, 0.0, 1.0), Colour::BLUE, 1.0, 5.0),
+		];
+		let lighting = PointLights {
+			lights: &lights,
+			ambient: 0.1,
+			camera: Vector3::new(0.0, 0.0, -1.0),
+		};
+		gbuffer.light(&lighting, &mut screen);
+
+		let corner = screen.get(2, height - 3);
+		let middle = screen.get(10, 20);
+		assert!(corner.red > middle.red && corner.red > 200);
+		assert_eq!(corner.blue, middle.blue);
+		assert_eq!(middle.blue, 25);
+		assert!(screen.get_depth(2, height - 3).is_finite());
+		// Pixels without any geometry aren't touched
+		assert_eq!(screen.get(width - 2, 1), Colour::BLACK);
+		assert_eq!(screen.get_depth(width - 2, 1), f32::INFINITY);
+	}
+}
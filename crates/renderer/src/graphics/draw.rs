@@ -4,6 +4,6 @@ pub trait Draw<VsOut: Interpolate> {
 	fn draw<T: Target, P: Pipeline<VsOut = VsOut, Fragment = T::Item>>(
 		&self,
 		target: &mut T,
-		pipeline: &mut P,
+		pipeline: &P,
 	);
 }
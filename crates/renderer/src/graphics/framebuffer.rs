@@ -0,0 +1,348 @@
+//! Multiple render targets
+//!
+//! A [`Framebuffer`] writes each fragment to several colour attachments, which all share one depth (and stencil) buffer.
+//! Pipelines that draw to a framebuffer output a tuple of values, one for each attachment.
+//! For example, a pipeline with `type Fragment = (Colour, u32)` can draw colours and object ids in a single pass to
+//! `Framebuffer::new((&mut colours[..], &mut ids[..]), &mut depth, width, height)`
+use rendy3d_maths::geometry::bounding_area::BoundingArea2D;
+
+#[cfg(feature = "std")]
+use crate::graphics::target::SplitTarget;
+use crate::graphics::target::Target;
+
+/// Colour attachments of a [`Framebuffer`]
+///
+/// Implemented for mutable slices, which hold one value for each pixel, and for tuples of up to 4 attachments
+pub trait Attachments {
+	type Item: Clone + Default;
+	fn set(&mut self, index: usize, value: Self::Item);
+	fn get(&self, index: usize) -> Self::Item;
+	fn fill(&mut self, value: Self::Item);
+}
+impl<T: Clone + Default> Attachments for &mut [T] {
+	type Item = T;
+	#[inline]
+	fn set(&mut self, index: usize, value: T) {
+		self[index] = value;
+	}
+	#[inline]
+	fn get(&self, index: usize) -> T {
+		self[index].clone()
+	}
+	fn fill(&mut self, value: T) {
+		<[T]>::fill(self, value);
+	}
+}
+macro_rules! tuple_attachments {
+	($($name:ident $index:tt),+) => {
+		impl<$($name: Attachments),+> Attachments for ($($name,)+) {
+			type Item = ($($name::Item,)+);
+			#[inline]
+			fn set(&mut self, index: usize, value: Self::Item) {
+				$(self.$index.set(index, value.$index);)+
+			}
+			#[inline]
+			fn get(&self, index: usize) -> Self::Item {
+				($(self.$index.get(index),)+)
+			}
+			fn fill(&mut self, value: Self::Item) {
+				$(self.$index.fill(value.$index);)+
+			}
+		}
+	};
+}
+tuple_attachments!(A 0);
+tuple_attachments!(A 0, B 1);
+tuple_attachments!(A 0, B 1, C 2);
+tuple_attachments!(A 0, B 1, C 2, D 3);
+
+/// Attachments that can be divided into disjoint bands, so that a [`Framebuffer`] can be drawn to from several threads
+#[cfg(feature = "std")]
+pub trait SplitAttachments: Attachments {
+	type Band<'a>: Attachments<Item = Self::Item> + Send
+	where
+		Self: 'a;
+	/// Splits the values from `start` to `end` into bands of (at most) `len` values each
+	fn split(&mut self, start: usize, end: usize, len: usize) -> Vec<Self::Band<'_>>;
+}
+#[cfg(feature = "std")]
+impl<T: Clone + Default + Send> SplitAttachments for &mut [T] {
+	type Band<'a>
+		= &'a mut [T]
+	where
+		Self: 'a;
+	fn split(&mut self, start: usize, end: usize, len: usize) -> Vec<&mut [T]> {
+		self[start..end].chunks_mut(len).collect()
+	}
+}
+#[cfg(feature = "std")]
+macro_rules! tuple_split_attachments {
+	($($name:ident $index:tt),+) => {
+		impl<$($name: SplitAttachments),+> SplitAttachments for ($($name,)+) {
+			type Band<'a>
+				= ($($name::Band<'a>,)+)
+			where
+				Self: 'a;
+			fn split(&mut self, start: usize, end: usize, len: usize) -> Vec<Self::Band<'_>> {
+				// Every attachment is split into the same number of bands, which are then zipped together
+				let mut bands = ($(self.$index.split(start, end, len).into_iter(),)+);
+				core::iter::from_fn(|| Some(($(bands.$index.next()?,)+))).collect()
+			}
+		}
+	};
+}
+#[cfg(feature = "std")]
+tuple_split_attachments!(A 0);
+#[cfg(feature = "std")]
+tuple_split_attachments!(A 0, B 1);
+#[cfg(feature = "std")]
+tuple_split_attachments!(A 0, B 1, C 2);
+#[cfg(feature = "std")]
+tuple_split_attachments!(A 0, B 1, C 2, D 3);
+
+/// [`Target`] with several colour attachments that share one depth buffer
+///
+/// Every attachment, and the depth buffer, must hold `width * height` values.
+/// Framebuffers can also be bands of rows split out of a larger framebuffer by [`SplitTarget::split`],
+/// in which case the buffers only hold the rows of the band, but co-ordinates are the same as in the whole framebuffer
+pub struct Framebuffer<'a, A: Attachments> {
+	pub attachments: A,
+	pub z_buffer: &'a mut [f32],
+	pub stencil_buffer: Option<&'a mut [u8]>,
+	width: usize,
+	/// Region covered by the buffers
+	area: BoundingArea2D,
+	pub draw_colour: A::Item,
+}
+impl<'a, A: Attachments> Framebuffer<'a, A> {
+	pub fn new(attachments: A, z_buffer: &'a mut [f32], width: usize, height: usize) -> Self {
+		Self {
+			attachments,
+			z_buffer,
+			stencil_buffer: None,
+			width,
+			area: BoundingArea2D::new(0, width, 0, height),
+			draw_colour: A::Item::default(),
+		}
+	}
+	/// Adds a stencil buffer, which must hold `width * height` values
+	pub fn with_stencil(mut self, stencil_buffer: &'a mut [u8]) -> Self {
+		self.stencil_buffer = Some(stencil_buffer);
+		self
+	}
+	#[inline]
+	fn index(&self, x: usize, y: usize) -> usize {
+		(y - self.area.min_y) * self.width + x
+	}
+}
+impl<'a, A: Attachments> Target for Framebuffer<'a, A> {
+	type Item = A::Item;
+
+	fn set(&mut self, x: usize, y: usize, value: Self::Item) {
+		let i = self.index(x, y);
+		self.attachments.set(i, value);
+	}
+
+	fn get(&self, x: usize, y: usize) -> Self::Item {
+		self.attachments.get(self.index(x, y))
+	}
+
+	fn set_depth(&mut self, x: usize, y: usize, value: f32) {
+		let i = self.index(x, y);
+		self.z_buffer[i] = value;
+	}
+
+	fn get_depth(&self, x: usize, y: usize) -> f32 {
+		self.z_buffer[self.index(x, y)]
+	}
+
+	fn clear(&mut self, fill: Self::Item) {
+		self.clear_depth();
+		self.clear_stencil(0);
+		self.attachments.fill(fill);
+	}
+
+	fn clear_depth(&mut self) {
+		self.z_buffer.fill(f32::INFINITY);
+	}
+
+	fn area(&self) -> BoundingArea2D {
+		self.area.clone()
+	}
+
+	fn draw_colour(&self) -> Self::Item {
+		self.draw_colour.clone()
+	}
+	fn set_draw_colour(&mut self, value: Self::Item) {
+		self.draw_colour = value;
+	}
+
+	fn get_stencil(&self, x: usize, y: usize) -> Option<u8> {
+		let i = self.index(x, y);
+		self.stencil_buffer.as_ref().map(|stencil| stencil[i])
+	}
+	fn set_stencil(&mut self, x: usize, y: usize, value: u8) {
+		let i = self.index(x, y);
+		if let Some(stencil) = &mut self.stencil_buffer {
+			stencil[i] = value;
+		}
+	}
+	fn clear_stencil(&mut self, value: u8) {
+		if let Some(stencil) = &mut self.stencil_buffer {
+			stencil.fill(value);
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl<'a, A> SplitTarget for Framebuffer<'a, A>
+where
+	A: SplitAttachments,
+	A::Item: Send,
+{
+	type Tile<'b>
+		= Framebuffer<'b, A::Band<'b>>
+	where
+		Self: 'b;
+
+	/// Splits every attachment, and the depth and stencil buffers, into bands of rows
+	fn split(&mut self, area: &BoundingArea2D, rows: usize) -> Vec<Self::Tile<'_>> {
+		let Some(area) = area.intersection(&self.area) else {
+			return Vec::new();
+		};
+		let start = (area.min_y - self.area.min_y) * self.width;
+		let end = (area.max_y - self.area.min_y) * self.width;
+		let len = rows * self.width;
+		let depths = self.z_buffer[start..end].chunks_mut(len);
+		let mut stencils = self
+			.stencil_buffer
+			.as_deref_mut()
+			.map(|stencil| stencil[start..end].chunks_mut(len));
+		self.attachments
+			.split(start, end, len)
+			.into_iter()
+			.zip(depths)
+			.enumerate()
+			.map(|(i, (attachments, z_buffer))| {
+				let min_y = area.min_y + i * rows;
+				let max_y = min_y + z_buffer.len() / self.width;
+				Framebuffer {
+					attachments,
+					z_buffer,
+					stencil_buffer: stencils.as_mut().and_then(Iterator::next),
+					width: self.width,
+					area: BoundingArea2D::new(area.min_x, area.max_x, min_y, max_y),
+					draw_colour: self.draw_colour.clone(),
+				}
+			})
+			.collect()
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+	use rendy3d_maths::geometry::triangle::Triangle;
+
+	use super::*;
+	use crate::{
+		graphics::{
+			colour::Colour,
+			geometry::{clipping::NoClip, point::AbsoluteScreenCoordinate},
+			geometry_3d::point::Point,
+			pipeline::Pipeline,
+		},
+		render::{render, render_parallel},
+	};
+
+	/// Outputs a colour and an object id for each fragment
+	struct Ids(u32);
+	impl Pipeline for Ids {
+		type VsOut = ();
+		type VsIn = ();
+		type Vertex = Point;
+		type Fragment = (Colour, u32);
+		type ClippingStrategy = NoClip;
+
+		fn vertex(&self, _index: usize, vertex: Point, _state: ()) -> (Point, ()) {
+			(vertex, ())
+		}
+		fn fragment(&self, _position: AbsoluteScreenCoordinate, _data: ()) -> (Colour, u32) {
+			(Colour::new(self.0 as u8 * 100, 0, 0, 255), self.0)
+		}
+	}
+
+	#[test]
+	fn attachments_share_depth() {
+		let (width, height) = (32, 32);
+		let mut colours = vec![Colour::BLACK; width * height];
+		let mut ids = vec![0; width * height];
+		let mut depth = vec![f32::INFINITY; width * height];
+		let mut framebuffer =
+			Framebuffer::new((&mut colours[..], &mut ids[..]), &mut depth, width, height);
+		let near = Triangle::new(
+			Point::new(-1.0, -1.0, 0.2),
+			Point::new(1.0, -1.0, 0.2),
+			Point::new(-1.0, 1.0, 0.2),
+		);
+		let far = Triangle::new(
+			Point::new(-3.0, -3.0, 0.8),
+			Point::new(5.0, -3.0, 0.8),
+			Point::new(-3.0, 5.0, 0.8),
+		);
+		render([near], &mut Ids(1), &mut framebuffer, ());
+		render([far], &mut Ids(2), &mut framebuffer, ());
+		// Neither attachment is written where the far triangle is hidden
+		for (colour, id) in colours.iter().zip(&ids) {
+			assert!(*id == 1 || *id == 2);
+			assert_eq!(colour.red, *id as u8 * 100);
+		}
+		assert!(ids.contains(&1) && ids.contains(&2));
+	}
+
+	#[test]
+	fn parallel_matches_single_threaded() {
+		let (width, height) = (32, 29);
+		let triangles = [
+			Triangle::new(
+				Point::new(-0.9, -0.8, 0.3),
+				Point::new(0.7, -0.2, 0.6),
+				Point::new(-0.4, 0.9, 0.5),
+			),
+			Triangle::new(
+				Point::new(-1.0, 0.5, 0.4),
+				Point::new(0.9, -0.9, 0.4),
+				Point::new(0.8, 0.8, 0.4),
+			),
+		];
+		let draw = |parallel: bool| {
+			let mut colours = vec![Colour::BLACK; width * height];
+			let mut ids = vec![0; width * height];
+			let mut depth = vec![f32::INFINITY; width * height];
+			let mut stencil = vec![0; width * height];
+			let mut framebuffer =
+				Framebuffer::new((&mut colours[..], &mut ids[..]), &mut depth, width, height)
+					.with_stencil(&mut stencil);
+			for (id, triangle) in triangles.iter().enumerate() {
+				if parallel {
+					render_parallel(
+						[triangle.clone()],
+						&mut Ids(id as u32 + 1),
+						&mut framebuffer,
+						(),
+					);
+				} else {
+					render(
+						[triangle.clone()],
+						&mut Ids(id as u32 + 1),
+						&mut framebuffer,
+						(),
+					);
+				}
+			}
+			(colours, ids, depth)
+		};
+		let (colours, ids, depth) = draw(false);
+		assert!(ids.contains(&1) && ids.contains(&2));
+		assert_eq!(draw(true), (colours, ids, depth));
+	}
+}
@@ -5,7 +5,7 @@ use rendy3d_maths::{
 	vector::vector4::Vector4,
 };
 
-use crate::graphics::interpolate::Interpolate;
+use crate::graphics::interpolate::{Interpolate, Weights};
 #[derive(Clone, Copy)]
 pub enum ClippingPlane {
 	NearZ,
@@ -36,15 +36,39 @@ impl ClippingPlane {
 			Self::NegY => Vector4::new(0.0, 1.0, 0.0, 1.0),
 		}
 	}
+	/// Returns `true` if the point is on the inside of this plane
+	pub fn contains(self, p: Vector4<f64>) -> bool {
+		p.dot_with(self.equation()) >= 0.0
+	}
 }
 pub trait TriangleClipper<T> {
-	type ClipResult: IntoIterator<Item = Option<Triangle<Vertex<T>>>>;
+	/// Returns `true` if a triangle with a vertex at `p` must be passed to [`TriangleClipper::clip`] before it can be rasterized
+	///
+	/// By default, this is any point outside of the view frustum
+	fn must_clip(p: Vector4<f64>) -> bool {
+		!ClippingPlane::clipping_planes()
+			.into_iter()
+			.all(|plane| plane.contains(p))
+	}
 	/// Clips triangle by each equation in turn
 	fn clip(triangle: Triangle<Vertex<T>>) -> impl Iterator<Item = Triangle<Vertex<T>>>;
 }
 
 pub struct SutherlandHodgman;
 type Vertex<T> = (Vector4<f64>, T);
+
+/// Interpolates the attributes of the point that is `t` of the way from `v0` to `v1` in clip space
+fn interpolate_attributes<T: Interpolate>(v0: &Vertex<T>, v1: &Vertex<T>, t: f64) -> T {
+	let u = 1.0 - t;
+	// Attributes are linear in clip space, but values without perspective are linear on the screen, where the point is `s` of the way along
+	let w = u * v0.0.w + t * v1.0.w;
+	let s = if w == 0.0 { t } else { t * v1.0.w / w };
+	let weights = Weights {
+		perspective: [u as f32, t as f32, 0.0],
+		screen: [(1.0 - s) as f32, s as f32, 0.0],
+	};
+	T::interpolate_screen(&v0.1, &v1.1, &v1.1, &weights)
+}
 impl SutherlandHodgman {
 	/// Compute intersection point with plane
 	fn intersection<T: Interpolate>(
@@ -54,14 +78,9 @@ impl SutherlandHodgman {
 		value1: f64,
 	) -> Vertex<T> {
 		let t = value0 / (value0 - value1);
-		let u = 1.0 - t;
 		// Interpolate vertices
 		let p = v0.0 + (v1.0 - v0.0) * t;
-
-		// Interpolate attributes
-		let a = T::interpolate2(&v0.1, &v1.1, t as f32, u as f32);
-
-		(p, a)
+		(p, interpolate_attributes(v0, v1, t))
 	}
 }
 impl SutherlandHodgman {
@@ -150,7 +169,6 @@ impl<T> TriangleClipper<T> for SutherlandHodgman
 where
 	T: Interpolate + Clone,
 {
-	type ClipResult = [Option<Triangle<Vertex<T>>>; 2];
 	fn clip(triangle: Triangle<Vertex<T>>) -> impl Iterator<Item = Triangle<Vertex<T>>> {
 		Self::clip_equation(triangle, ClippingPlane::NearZ.equation())
 			.into_iter()
@@ -168,9 +186,87 @@ where
 	}
 }
 
+/// Size of the guard band used by [`GuardBand`], as a multiple of the size of the view frustum
+pub const GUARD_BAND: f64 = 256.0;
+
+/// Clipping strategy that only clips triangles against the near and far planes
+///
+/// Triangles that extend past the sides of the view frustum are left to the rasterizer, which only visits pixels within the target.
+/// This avoids generating lots of extra triangles, but requires a rasterizer that supports signed screen co-ordinates,
+/// such as [`EdgeFunctionRasterizer`](crate::graphics::rasterizer::edge_function_rasterizer::EdgeFunctionRasterizer).
+///
+/// Triangles that extend past the guard band (see [`GUARD_BAND`]) are still clipped against it,
+/// so that screen co-ordinates stay within the range that the rasterizer can represent
+pub struct GuardBand;
+impl GuardBand {
+	const PLANES: [Vector4<f64>; 4] = [
+		Vector4::new(1.0, 0.0, 0.0, GUARD_BAND),
+		Vector4::new(-1.0, 0.0, 0.0, GUARD_BAND),
+		Vector4::new(0.0, 1.0, 0.0, GUARD_BAND),
+		Vector4::new(0.0, -1.0, 0.0, GUARD_BAND),
+	];
+}
+impl<T> TriangleClipper<T> for GuardBand
+where
+	T: Interpolate + Clone,
+{
+	fn must_clip(p: Vector4<f64>) -> bool {
+		!(ClippingPlane::NearZ.contains(p)
+			&& ClippingPlane::FarZ.contains(p)
+			&& Self::PLANES
+				.into_iter()
+				.all(|plane| p.dot_with(plane) >= 0.0))
+	}
+	fn clip(triangle: Triangle<Vertex<T>>) -> impl Iterator<Item = Triangle<Vertex<T>>> {
+		let [neg_x, pos_x, neg_y, pos_y] = Self::PLANES;
+		SutherlandHodgman::clip_equation(triangle, ClippingPlane::NearZ.equation())
+			.into_iter()
+			.flatten()
+			.flat_map(|t| SutherlandHodgman::clip_equation(t, ClippingPlane::FarZ.equation()))
+			.flatten()
+			.flat_map(move |t| SutherlandHodgman::clip_equation(t, neg_x))
+			.flatten()
+			.flat_map(move |t| SutherlandHodgman::clip_equation(t, pos_x))
+			.flatten()
+			.flat_map(move |t| SutherlandHodgman::clip_equation(t, neg_y))
+			.flatten()
+			.flat_map(move |t| SutherlandHodgman::clip_equation(t, pos_y))
+			.flatten()
+	}
+}
+
+/// Clips a line against every plane of the view frustum
+///
+/// Returns `None` if the line is entirely outside of the view frustum
+pub fn clip_line<T: Interpolate>([a, b]: [Vertex<T>; 2]) -> Option<[Vertex<T>; 2]> {
+	// Range of the line (from `a` to `b`) that is inside the frustum
+	let mut start: f64 = 0.0;
+	let mut end: f64 = 1.0;
+	for plane in ClippingPlane::clipping_planes() {
+		let equation = plane.equation();
+		let d0 = a.0.dot_with(equation);
+		let d1 = b.0.dot_with(equation);
+		if d0 < 0.0 && d1 < 0.0 {
+			return None;
+		}
+		if d0 < 0.0 {
+			start = start.max(d0 / (d0 - d1));
+		} else if d1 < 0.0 {
+			end = end.min(d0 / (d0 - d1));
+		}
+	}
+	if start > end {
+		return None;
+	}
+	let at = |t: f64| (a.0 + (b.0 - a.0) * t, interpolate_attributes(&a, &b, t));
+	Some([at(start), at(end)])
+}
+
 pub struct NoClip;
 impl<T> TriangleClipper<T> for NoClip {
-	type ClipResult = [Option<Triangle<Vertex<T>>>; 1];
+	fn must_clip(_p: Vector4<f64>) -> bool {
+		false
+	}
 
 	fn clip(triangle: Triangle<Vertex<T>>) -> impl Iterator<Item = Triangle<Vertex<T>>> {
 		Some(triangle).into_iter()
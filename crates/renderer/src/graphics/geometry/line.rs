@@ -13,7 +13,7 @@ impl Line {
 	}
 	fn draw_line_low<T: Target, U: Interpolate, P: Pipeline<VsOut = U, Fragment = T::Item>>(
 		target: &mut T,
-		pipeline: &mut P,
+		pipeline: &P,
 		start: AbsoluteScreenCoordinate,
 		end: AbsoluteScreenCoordinate,
 	) {
@@ -37,7 +37,7 @@ impl Line {
 	}
 	fn draw_line_high<T: Target, U: Interpolate, P: Pipeline<VsOut = U, Fragment = T::Item>>(
 		target: &mut T,
-		pipeline: &mut P,
+		pipeline: &P,
 		start: AbsoluteScreenCoordinate,
 		end: AbsoluteScreenCoordinate,
 	) {
@@ -62,7 +62,7 @@ impl Line {
 	pub fn draw_line<T: Target, U: Interpolate, S: Pipeline<VsOut = U, Fragment = T::Item>>(
 		&self,
 		target: &mut T,
-		pipeline: &mut S,
+		pipeline: &S,
 		start: AbsoluteScreenCoordinate,
 		end: AbsoluteScreenCoordinate,
 	) {
@@ -83,9 +83,10 @@ impl<VsOut: Interpolate> Draw<VsOut> for Line {
 	fn draw<T: Target, P: Pipeline<VsOut = VsOut, Fragment = T::Item>>(
 		&self,
 		target: &mut T,
-		pipeline: &mut P,
+		pipeline: &P,
 	) {
-		if target.point_below_z_buffer(self.start) && target.point_below_z_buffer(self.end) {
+		let compare = pipeline.depth_state().compare;
+		if !target.depth_test(self.start, compare) && !target.depth_test(self.end, compare) {
 			return;
 		}
 		self.draw_line(target, pipeline, self.start, self.end);
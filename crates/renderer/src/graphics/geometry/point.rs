@@ -1,6 +1,11 @@
 use derive_more::Add;
 
-use crate::graphics::{draw::Draw, interpolate::Interpolate, pipeline::Pipeline, target::Target};
+use crate::graphics::{
+	draw::Draw,
+	interpolate::Interpolate,
+	pipeline::{Pipeline, stencil::stencil_test},
+	target::Target,
+};
 
 #[derive(Clone, Add, Copy, Debug)]
 pub struct AbsoluteScreenCoordinate {
@@ -25,15 +30,80 @@ impl AbsoluteScreenCoordinate {
 	}
 }
 
+/// Screen co-ordinate with sub-pixel precision
+///
+/// Pixel `(x, y)` covers the area from `(x, y)` to `(x + 1, y + 1)`, so its centre is at `(x + 0.5, y + 0.5)`
+#[derive(Clone, Copy, Debug)]
+pub struct ScreenCoordinate {
+	pub x: f32,
+	pub y: f32,
+	pub z: f32,
+	/// Reciprocal of the clip-space `w` of the vertex, which is used for perspective-correct interpolation
+	pub w_reciprocal: f32,
+}
+impl ScreenCoordinate {
+	pub const fn new(x: f32, y: f32, z: f32) -> Self {
+		Self {
+			x,
+			y,
+			z,
+			w_reciprocal: 1.0,
+		}
+	}
+	pub const fn with_w(mut self, w: f32) -> Self {
+		self.w_reciprocal = 1.0 / w;
+		self
+	}
+	/// Snaps this co-ordinate to the pixel that contains it
+	pub const fn to_absolute(self) -> AbsoluteScreenCoordinate {
+		AbsoluteScreenCoordinate::new(self.x as usize, self.y as usize, self.z)
+	}
+}
+
 pub static mut MAX_Z: f32 = 0.0;
-impl<VsOut: Interpolate> Draw<VsOut> for AbsoluteScreenCoordinate {
-	fn draw<T: Target, P: Pipeline>(&self, target: &mut T, _pipeline: &mut P) {
-		// Record Z in Z buffer if point is above Z buffer
-		if !target.point_below_z_buffer(*self) {
+impl AbsoluteScreenCoordinate {
+	/// Runs the stencil and depth tests for this point, and writes its depth if they pass
+	#[inline]
+	pub(crate) fn depth_stencil_test<T: Target, P: Pipeline>(
+		&self,
+		target: &mut T,
+		pipeline: &P,
+	) -> bool {
+		let depth = pipeline.depth_state();
+		if !stencil_test(target, pipeline.stencil_state(), self.x, self.y, |target| {
+			target.depth_test(*self, depth.compare)
+		}) {
+			return false;
+		}
+		// Record Z in Z buffer if the point passed the depth test
+		if depth.write {
 			target.set_z_in_z_buffer(*self);
-		} else {
+		}
+		true
+	}
+	/// Blends `fragment` with the pixel in the target, and writes the result
+	#[inline]
+	pub(crate) fn write_fragment<T: Target, P: Pipeline<Fragment = T::Item>>(
+		&self,
+		target: &mut T,
+		pipeline: &P,
+		fragment: T::Item,
+	) {
+		let colour = pipeline
+			.blend()
+			.apply(fragment, || target.get(self.x, self.y));
+		target.draw_point(*self, colour);
+	}
+}
+impl<VsOut: Interpolate> Draw<VsOut> for AbsoluteScreenCoordinate {
+	fn draw<T: Target, P: Pipeline<VsOut = VsOut, Fragment = T::Item>>(
+		&self,
+		target: &mut T,
+		pipeline: &P,
+	) {
+		if !target.contains_point(*self) || !self.depth_stencil_test(target, pipeline) {
 			return;
 		}
-		target.draw_point(*self, target.draw_colour());
+		self.write_fragment(target, pipeline, target.draw_colour());
 	}
 }
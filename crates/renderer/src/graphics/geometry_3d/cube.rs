@@ -1,4 +1,4 @@
-use std::array::IntoIter;
+use core::array::IntoIter;
 
 use rendy3d_maths::geometry::triangle::Triangle;
 
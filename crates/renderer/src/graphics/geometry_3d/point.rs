@@ -1,47 +1,84 @@
-use core::ops::MulAssign;
+use core::ops::{Add, MulAssign, Sub};
 
-use crate::maths::{matrices::matrix4::Matrix4, vector::vector3::Vector3};
-use derive_more::{Add, Deref, DerefMut, Sub};
+use crate::maths::{
+	matrices::matrix4::Matrix4,
+	vector::{vector3::Vector3, vector4::Vector4},
+};
+use derive_more::{Deref, DerefMut};
 use rendy3d_maths::geometry::bounding_area::BoundingArea2D;
 
-use crate::graphics::geometry::point::AbsoluteScreenCoordinate;
+use crate::graphics::geometry::point::ScreenCoordinate;
 
 /// Coordinates between -1 and 1
-#[derive(Deref, DerefMut, Clone, Add, Copy, Sub, Debug)]
-pub struct Point(Vector3<f64>);
+///
+/// Points that are projected with [`Point::apply`] keep the `w` that they were divided by,
+/// so that they can be clipped and interpolated across with perspective. Other points have a `w` of 1
+#[derive(Deref, DerefMut, Clone, Copy, Debug)]
+pub struct Point {
+	#[deref]
+	#[deref_mut]
+	position: Vector3<f64>,
+	w: f64,
+}
 
 impl Point {
 	pub const fn new(x: f64, y: f64, z: f64) -> Self {
-		Self(Vector3::new(x, y, z))
+		Self::from_vector(Vector3::new(x, y, z))
+	}
+	pub const fn from_vector(v: Vector3<f64>) -> Self {
+		Self {
+			position: v,
+			w: 1.0,
+		}
+	}
+	/// Divides a position in clip space by its `w`, which is kept
+	pub fn from_clip_space(v: Vector4<f64>) -> Self {
+		Self {
+			position: Vector3::from_homogenous(v),
+			w: v.w,
+		}
 	}
-	pub fn from_vector(v: Vector3<f64>) -> Self {
-		Self(v)
+	/// Position in clip space, before it was divided by `w`
+	pub fn to_clip_space(self) -> Vector4<f64> {
+		let mut v = self.position.to_homogenous();
+		v *= self.w;
+		v
+	}
+	pub const fn w(&self) -> f64 {
+		self.w
 	}
 	pub fn to_vector(self) -> Vector3<f64> {
 		Vector3::new(self.x, self.y, self.z)
 	}
-	pub fn to_pixel_coordinate(self, target_area: BoundingArea2D) -> AbsoluteScreenCoordinate {
-		let width = target_area.width();
-		let height = target_area.height();
-		let offset = AbsoluteScreenCoordinate::new(
-			target_area.min_x + (width / 2),
-			target_area.min_y + (height / 2),
-			0.0,
-		);
-		let x = (offset.x as f64 + self.x * (width as f64) / 2.0) as usize;
-		let y = (offset.y as f64 - self.y * (height as f64) / 2.0) as usize;
-		// println!("({}, {}, {})", x, y, self.z);
-		AbsoluteScreenCoordinate::new(x, y, self.z as f32)
+	/// Converts from normalised device co-ordinates to a position on the screen, keeping sub-pixel precision
+	pub fn to_screen_coordinate(self, target_area: BoundingArea2D) -> ScreenCoordinate {
+		let half_width = target_area.width() as f64 / 2.0;
+		let half_height = target_area.height() as f64 / 2.0;
+		let x = target_area.min_x as f64 + half_width + self.x * half_width;
+		let y = target_area.min_y as f64 + half_height - self.y * half_height;
+		ScreenCoordinate::new(x as f32, y as f32, self.z as f32).with_w(self.w as f32)
 	}
 	pub fn apply(self, transformation: Matrix4<f64>) -> Point {
-		Point::from_vector(Vector3::from_homogenous(
-			transformation * self.to_homogenous(),
-		))
+		Point::from_clip_space(transformation * self.to_clip_space())
 	}
 }
 
 impl MulAssign<Matrix4<f64>> for Point {
 	fn mul_assign(&mut self, rhs: Matrix4<f64>) {
-		*self = Point::from_vector(Vector3::from_homogenous(rhs * self.to_homogenous()));
+		*self = self.apply(rhs);
+	}
+}
+
+/// Points are added and subtracted as positions, so the result has a `w` of 1
+impl Add for Point {
+	type Output = Self;
+	fn add(self, rhs: Self) -> Self {
+		Self::from_vector(self.position + rhs.position)
+	}
+}
+impl Sub for Point {
+	type Output = Self;
+	fn sub(self, rhs: Self) -> Self {
+		Self::from_vector(self.position - rhs.position)
 	}
 }
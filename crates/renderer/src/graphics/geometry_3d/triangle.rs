@@ -21,6 +21,7 @@ where
 	}
 }
 // This is dumb so will be fixed at some point!
+#[expect(dead_code)]
 trait Normal {
 	fn normal(&self) -> Vector3<f64>;
 }
@@ -3,11 +3,63 @@ use core::ops::{Add, AddAssign, Mul};
 
 use crate::maths::vector::{vector2::Vector2, vector3::Vector3};
 
+/// Derives [`Interpolate`] for structs, field by field
+pub use rendy3d_derive::Interpolate;
+
 /// Represents types that can be interpolated
-pub trait Interpolate {
+///
+/// Values are interpolated perspective-correctly across triangles and lines on the screen.
+/// Wrap a value in [`NoPerspective`] to interpolate it linearly in screen space instead, or in [`Static`] to not interpolate it at all
+pub trait Interpolate: Sized {
 	/// Interpolate 3 instances of an interpolatable type with 3 weights
 	fn interpolate3(a: &Self, b: &Self, c: &Self, x: f32, y: f32, z: f32) -> Self;
 	fn interpolate2(a: &Self, b: &Self, x: f32, y: f32) -> Self;
+	/// Interpolate across a triangle on the screen
+	///
+	/// By default, the perspective-correct weights are used. Types that hold other interpolatable values should pass `weights` on to them
+	fn interpolate_screen(a: &Self, b: &Self, c: &Self, weights: &Weights) -> Self {
+		let [x, y, z] = weights.perspective;
+		Self::interpolate3(a, b, c, x, y, z)
+	}
+	/// Difference between two values (`a - b`), which is used to calculate screen-space derivatives
+	///
+	/// By default, this interpolates with weights of 1 and -1, which is only correct for types that can hold negative values
+	fn difference(a: &Self, b: &Self) -> Self {
+		Self::interpolate2(a, b, 1.0, -1.0)
+	}
+}
+
+/// Weights of the vertices of a triangle at a point on the screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Weights {
+	/// Weights that account for perspective, which are linear in clip space
+	pub perspective: [f32; 3],
+	/// Barycentric co-ordinates in screen space, which ignore perspective
+	pub screen: [f32; 3],
+}
+impl Weights {
+	/// Corrects the screen-space barycentric co-ordinates of a point, using the reciprocal of each vertex's clip-space `w`
+	#[inline]
+	pub fn new(screen: [f32; 3], w_reciprocal: [f32; 3]) -> Self {
+		let [x, y, z] = [0, 1, 2].map(|i| screen[i] * w_reciprocal[i]);
+		let total = x + y + z;
+		let perspective = if total == 0.0 {
+			screen
+		} else {
+			[x / total, y / total, z / total]
+		};
+		Self {
+			perspective,
+			screen,
+		}
+	}
+	/// Weights without any perspective, e.g. for geometry that was never projected
+	pub const fn linear(weights: [f32; 3]) -> Self {
+		Self {
+			perspective: weights,
+			screen: weights,
+		}
+	}
 }
 
 impl Interpolate for f32 {
@@ -48,6 +100,15 @@ where
 			U::interpolate2(&a.1, &b.1, x, y),
 		)
 	}
+	fn interpolate_screen(a: &Self, b: &Self, c: &Self, weights: &Weights) -> Self {
+		(
+			T::interpolate_screen(&a.0, &b.0, &c.0, weights),
+			U::interpolate_screen(&a.1, &b.1, &c.1, weights),
+		)
+	}
+	fn difference(a: &Self, b: &Self) -> Self {
+		(T::difference(&a.0, &b.0), U::difference(&a.1, &b.1))
+	}
 }
 
 impl<T, U, V> Interpolate for (T, U, V)
@@ -70,6 +131,20 @@ where
 			V::interpolate2(&a.2, &b.2, x, y),
 		)
 	}
+	fn interpolate_screen(a: &Self, b: &Self, c: &Self, weights: &Weights) -> Self {
+		(
+			T::interpolate_screen(&a.0, &b.0, &c.0, weights),
+			U::interpolate_screen(&a.1, &b.1, &c.1, weights),
+			V::interpolate_screen(&a.2, &b.2, &c.2, weights),
+		)
+	}
+	fn difference(a: &Self, b: &Self) -> Self {
+		(
+			T::difference(&a.0, &b.0),
+			U::difference(&a.1, &b.1),
+			V::difference(&a.2, &b.2),
+		)
+	}
 }
 
 // Implement for Vector types
@@ -113,49 +188,52 @@ where
 }
 
 /// Structure for simplifying perspective correct interpolation
+///
+/// Every [`Interpolate`] value is now interpolated perspective-correctly, so this just holds the value and forwards to it
+#[deprecated(note = "values are now interpolated perspective-correctly without a wrapper")]
 #[derive(Clone)]
 pub struct PerspectiveCorrectInterpolate<T: Interpolate + Mul<f64, Output = T>> {
 	data: T,
-	z_reciprocal: f64,
 }
+#[allow(deprecated)]
 impl<T> PerspectiveCorrectInterpolate<T>
 where
 	T: Interpolate + Mul<f64, Output = T>,
 {
-	pub fn new(data: T, z: f64) -> Self {
-		let z_reciprocal = 1.0 / z;
-		// Interpolate data/z and 1/z
-		// as 1/z is linear when an object is perspective projected
-		Self {
-			data: data * z_reciprocal,
-			z_reciprocal,
-		}
+	pub fn new(data: T, _z: f64) -> Self {
+		Self { data }
 	}
 
 	/// Gets the interpolated value
 	pub fn get(self) -> T {
-		let z = 1.0 / self.z_reciprocal;
-		self.data * z
+		self.data
 	}
 }
 
+#[allow(deprecated)]
 impl<T> Interpolate for PerspectiveCorrectInterpolate<T>
 where
 	T: Interpolate + Mul<f64, Output = T> + Copy,
 {
 	fn interpolate3(a: &Self, b: &Self, c: &Self, x: f32, y: f32, z: f32) -> Self {
 		let data = T::interpolate3(&a.data, &b.data, &c.data, x, y, z);
-		let z_reciprocal =
-			f64::interpolate3(&a.z_reciprocal, &b.z_reciprocal, &c.z_reciprocal, x, y, z);
-		Self { data, z_reciprocal }
+		Self { data }
 	}
 	fn interpolate2(a: &Self, b: &Self, x: f32, y: f32) -> Self {
 		let data = T::interpolate2(&a.data, &b.data, x, y);
-		let z_reciprocal = f64::interpolate2(&a.z_reciprocal, &b.z_reciprocal, x, y);
-		Self { data, z_reciprocal }
+		Self { data }
+	}
+	fn interpolate_screen(a: &Self, b: &Self, c: &Self, weights: &Weights) -> Self {
+		let data = T::interpolate_screen(&a.data, &b.data, &c.data, weights);
+		Self { data }
+	}
+	fn difference(a: &Self, b: &Self) -> Self {
+		let data = T::difference(&a.data, &b.data);
+		Self { data }
 	}
 }
 
+/// Value that is the same across a whole primitive (flat shading), taken from its first vertex
 #[derive(Clone, Copy)]
 pub struct Static<T> {
 	data: T,
@@ -181,4 +259,171 @@ where
 		// If static, a should be equal to b
 		*a
 	}
+	fn difference(a: &Self, _b: &Self) -> Self {
+		// A flat value has no meaningful derivative, so it is passed through unchanged
+		*a
+	}
+}
+
+/// Value that is interpolated linearly in screen space, ignoring perspective
+///
+/// This is cheaper to shade with, and is useful for effects that should line up with the screen (e.g. screen-space patterns)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct NoPerspective<T>(pub T);
+impl<T> NoPerspective<T> {
+	pub const fn new(data: T) -> Self {
+		Self(data)
+	}
+	pub fn get(self) -> T {
+		self.0
+	}
+}
+
+impl<T> Interpolate for NoPerspective<T>
+where
+	T: Interpolate,
+{
+	fn interpolate3(a: &Self, b: &Self, c: &Self, x: f32, y: f32, z: f32) -> Self {
+		Self(T::interpolate3(&a.0, &b.0, &c.0, x, y, z))
+	}
+	fn interpolate2(a: &Self, b: &Self, x: f32, y: f32) -> Self {
+		Self(T::interpolate2(&a.0, &b.0, x, y))
+	}
+	fn interpolate_screen(a: &Self, b: &Self, c: &Self, weights: &Weights) -> Self {
+		// Everything inside is interpolated in screen space too
+		Self(T::interpolate_screen(
+			&a.0,
+			&b.0,
+			&c.0,
+			&Weights::linear(weights.screen),
+		))
+	}
+	fn difference(a: &Self, b: &Self) -> Self {
+		Self(T::difference(&a.0, &b.0))
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+	use rendy3d_maths::{geometry::triangle::Triangle, matrices::matrix4::Matrix4};
+
+	use super::*;
+	use crate::{
+		graphics::{
+			geometry::{clipping::SutherlandHodgman, point::AbsoluteScreenCoordinate},
+			geometry_3d::point::Point,
+			pipeline::Pipeline,
+			screen::Screen,
+		},
+		render::render,
+	};
+
+	/// Outputs how far along the floor each fragment is, with and without perspective correction
+	struct Floor(Matrix4<f64>);
+	impl Pipeline for Floor {
+		type VsOut = (f32, NoPerspective<f32>);
+		type VsIn = ();
+		type Vertex = Point;
+		type Fragment = (f32, f32);
+		type ClippingStrategy = SutherlandHodgman;
+
+		fn vertex(&self, _index: usize, vertex: Point, _state: ()) -> (Point, Self::VsOut) {
+			// 0 at the near edge of the floor, and 1 at the far edge
+			let distance = ((-vertex.z - 1.0) / 8.0) as f32;
+			(
+				vertex.apply(self.0.clone()),
+				(distance, NoPerspective(distance)),
+			)
+		}
+		fn fragment(&self, _position: AbsoluteScreenCoordinate, data: Self::VsOut) -> (f32, f32) {
+			(data.0, data.1.get())
+		}
+	}
+
+	#[derive(Clone, Copy, Debug, PartialEq, Interpolate)]
+	struct Varyings {
+		uv: Vector2<f32>,
+		#[interpolate(no_perspective)]
+		screen: f32,
+		#[interpolate(flat)]
+		id: u32,
+	}
+	#[derive(Clone, Copy, Debug, PartialEq, Interpolate)]
+	struct Wrapper(Varyings, #[interpolate(flat)] f32);
+
+	#[test]
+	fn derived_fields_are_interpolated_by_mode() {
+		let vertex = |i: u32| {
+			Wrapper(
+				Varyings {
+					uv: Vector2::new(i as f32, 0.0),
+					screen: i as f32,
+					id: i + 1,
+				},
+				i as f32,
+			)
+		};
+		let (a, b, c) = (vertex(0), vertex(1), vertex(2));
+		let weights = Weights {
+			perspective: [0.0, 1.0, 0.0],
+			screen: [0.0, 0.0, 1.0],
+		};
+		let value = Wrapper::interpolate_screen(&a, &b, &c, &weights);
+		assert_eq!(value.0.uv, Vector2::new(1.0, 0.0));
+		assert_eq!(value.0.screen, 2.0);
+		assert_eq!(value.0.id, 1);
+		assert_eq!(value.1, 0.0);
+		// Without separate screen weights, every interpolated field uses the same weights
+		let value = Wrapper::interpolate2(&a, &c, 0.5, 0.5);
+		assert_eq!((value.0.uv.x, value.0.screen, value.0.id), (1.0, 1.0, 1));
+		// Flat fields have no derivative, so their difference is the value itself
+		let value = Wrapper::difference(&c, &a);
+		assert_eq!((value.0.uv.x, value.0.screen, value.0.id), (2.0, 2.0, 3));
+		assert_eq!(value.1, 2.0);
+	}
+
+	#[test]
+	fn values_are_perspective_correct() {
+		let size = 64;
+		let mut frame = vec![(-1.0, -1.0); size * size];
+		let mut depth = vec![f32::INFINITY; size * size];
+		let mut screen = Screen::new(&mut frame, &mut depth, size, size);
+		let (a, b, c, d) = (
+			Point::new(-1.0, -0.5, -1.0),
+			Point::new(1.0, -0.5, -1.0),
+			Point::new(1.0, -0.5, -9.0),
+			Point::new(-1.0, -0.5, -9.0),
+		);
+		// The sides of the floor are off the screen, so it is clipped too
+		let projection = Matrix4::new_perspective(1.2, 1.2, 20.0, 0.1);
+		render(
+			[Triangle::new(a, b, c), Triangle::new(a, c, d)],
+			&mut Floor(projection),
+			&mut screen,
+			(),
+		);
+
+		let column = (0..size)
+			.map(|y| frame[y * size + size / 2])
+			.collect::<Vec<_>>();
+		let top = column.iter().position(|v| v.0 >= 0.0).unwrap();
+		let bottom = column.iter().rposition(|v| v.0 >= 0.0).unwrap();
+		let focal_length = 1.0 / f64::tan(0.6);
+		// Row on the screen that is at a distance along the floor
+		let row = |distance: f64| (1.0 + 0.5 * focal_length / distance) * 0.5 * size as f64;
+		let (near, far) = (row(1.0), row(9.0));
+		for (y, (perspective, linear)) in column.iter().enumerate().take(bottom + 1).skip(top) {
+			// Distance to the floor through the centre of the pixel
+			let centre = y as f64 + 0.5;
+			let distance = 0.5 * focal_length / (centre / size as f64 * 2.0 - 1.0);
+			let expected = ((distance - 1.0) / 8.0) as f32;
+			assert!(
+				(perspective - expected).abs() < 0.01,
+				"{y}: {perspective} {expected}"
+			);
+			// Values without perspective change at the same rate on every row
+			let expected = ((near - centre) / (near - far)) as f32;
+			assert!((linear - expected).abs() < 0.01, "{y}: {linear} {expected}");
+		}
+	}
 }
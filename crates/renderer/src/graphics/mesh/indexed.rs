@@ -1,9 +1,11 @@
 //! Indexed mesh
 
-use core::slice::ChunksExact;
+use core::slice::Iter;
 
 use rendy3d_maths::geometry::triangle::Triangle;
 
+use crate::graphics::primitive::{Topology, TriangleAssembler};
+
 /// Indexed mesh type
 ///
 /// The vertices are stored in a separate [`Vec`] to the indices. This means that no vertex is duplicated, even if it appears in multiple triangles.
@@ -12,34 +14,125 @@ use rendy3d_maths::geometry::triangle::Triangle;
 pub struct IndexedMesh<T, I = u16> {
 	pub vertices: Vec<T>,
 	pub indices: Vec<I>,
+	/// How the indices are assembled into triangles
+	pub topology: Topology,
+	/// Index that ends the current strip or fan, so that the next index starts a new one
+	pub primitive_restart: Option<I>,
+}
+
+/// Iterator over the indices of the vertices of each triangle in an [`IndexedMesh`]
+#[derive(Clone)]
+pub struct TriangleIndices<'a, I> {
+	indices: Iter<'a, I>,
+	primitive_restart: Option<I>,
+	assembler: TriangleAssembler<usize>,
+}
+impl<I> Iterator for TriangleIndices<'_, I>
+where
+	I: Into<usize> + Copy + PartialEq,
+{
+	type Item = Triangle<usize>;
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			let index = *self.indices.next()?;
+			if self.primitive_restart == Some(index) {
+				self.assembler.restart();
+				continue;
+			}
+			if let Some(triangle) = self.assembler.push(index.into()) {
+				return Some(triangle);
+			}
+		}
+	}
 }
 
+#[derive(Clone)]
 pub struct IndexedMeshIter<'a, T, I> {
-	mesh: &'a IndexedMesh<T, I>,
-	chunks: ChunksExact<'a, I>,
+	vertices: &'a [T],
+	indices: TriangleIndices<'a, I>,
 }
 impl<T, I> Iterator for IndexedMeshIter<'_, T, I>
 where
 	T: Clone,
-	I: Into<usize> + Copy,
+	I: Into<usize> + Copy + PartialEq,
 {
 	type Item = Triangle<T>;
 	fn next(&mut self) -> Option<Self::Item> {
-		let chunk = self.chunks.next()?;
-		let i1 = chunk[0];
-		let i2 = chunk[1];
-		let i3 = chunk[2];
-		let v1 = self.mesh.vertices[i1.into()].clone();
-		let v2 = self.mesh.vertices[i2.into()].clone();
-		let v3 = self.mesh.vertices[i3.into()].clone();
-		let triangle = Triangle::new(v1, v2, v3);
-		Some(triangle)
+		let triangle = self.indices.next()?;
+		Some(triangle.map_vertices(|i| self.vertices[i].clone()))
 	}
 }
 impl<T, I> IndexedMesh<T, I> {
+	/// Creates a mesh with the [`TriangleList`](Topology::TriangleList) topology
+	pub const fn new(vertices: Vec<T>, indices: Vec<I>) -> Self {
+		Self {
+			vertices,
+			indices,
+			topology: Topology::TriangleList,
+			primitive_restart: None,
+		}
+	}
+	pub fn with_topology(mut self, topology: Topology) -> Self {
+		self.topology = topology;
+		self
+	}
+	pub fn with_primitive_restart(mut self, index: I) -> Self {
+		self.primitive_restart = Some(index);
+		self
+	}
 	/// Returns an iterator over the triangles in this mesh
-	pub fn triangles(&self) -> IndexedMeshIter<'_, T, I> {
-		let chunks = self.indices.chunks_exact(3);
-		IndexedMeshIter { mesh: self, chunks }
+	pub fn triangles(&self) -> IndexedMeshIter<'_, T, I>
+	where
+		I: Copy,
+	{
+		IndexedMeshIter {
+			vertices: &self.vertices,
+			indices: self.triangle_indices(),
+		}
+	}
+	/// Returns an iterator over the indices of the vertices of each triangle in this mesh
+	pub fn triangle_indices(&self) -> TriangleIndices<'_, I>
+	where
+		I: Copy,
+	{
+		TriangleIndices {
+			indices: self.indices.iter(),
+			primitive_restart: self.primitive_restart,
+			assembler: TriangleAssembler::new(self.topology),
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	/// Twice the signed area of a triangle of 2D points, which is positive when the vertices are anticlockwise
+	fn winding(t: &Triangle<(f32, f32)>) -> f32 {
+		let (a, b, c) = (t.vertex1, t.vertex2, t.vertex3);
+		(b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+	}
+
+	#[test]
+	fn strips_keep_winding_order() {
+		// Two rows of vertices, zig-zagging between them
+		let vertices = (0..8).map(|i| ((i / 2) as f32, (i % 2) as f32)).collect();
+		let mesh = IndexedMesh::new(vertices, vec![0_u16, 1, 2, 3, 0xffff, 4, 5, 6, 7])
+			.with_topology(Topology::TriangleStrip)
+			.with_primitive_restart(0xffff);
+		let triangles = mesh.triangles().collect::<Vec<_>>();
+		// The restart drops the triangles that would join the two strips
+		assert_eq!(triangles.len(), 4);
+		assert!(triangles.iter().all(|t| winding(t) < 0.0));
+	}
+
+	#[test]
+	fn fans_keep_winding_order() {
+		let vertices = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0), (-1.0, 1.0)];
+		let mesh = IndexedMesh::new(vertices.to_vec(), vec![0_u16, 1, 2, 3, 4])
+			.with_topology(Topology::TriangleFan);
+		let triangles = mesh.triangles().collect::<Vec<_>>();
+		assert_eq!(triangles.len(), 3);
+		assert!(triangles.iter().all(|t| winding(t) > 0.0));
 	}
 }
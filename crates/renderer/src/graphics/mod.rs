@@ -1,13 +1,18 @@
 pub mod camera;
 pub mod colour;
+pub mod deferred;
 pub mod draw;
+pub mod framebuffer;
 pub mod geometry;
 pub mod geometry_3d;
 pub mod interpolate;
 pub mod mesh;
+pub mod multisample;
 #[cfg(feature = "std")]
 pub mod object;
 pub mod pipeline;
+pub mod post_process;
+pub mod primitive;
 pub mod rasterizer;
 pub mod screen;
 pub mod target;
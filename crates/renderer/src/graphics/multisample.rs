@@ -0,0 +1,347 @@
+//! Multisample anti-aliasing
+//!
+//! Triangles are rasterized into a [`MultisampleScreen`], which stores coverage and depth for several samples in each pixel.
+//! The fragment shader still only runs once per pixel, and its output is written to every sample that the triangle covers.
+//! Once everything has been drawn, [`MultisampleScreen::resolve`] averages the samples into a normal [`Screen`](crate::graphics::screen::Screen)
+use core::ops::Range;
+
+use rendy3d_maths::geometry::bounding_area::BoundingArea2D;
+
+use crate::graphics::{colour::Colour, target::Target};
+#[cfg(feature = "std")]
+use crate::graphics::{screen::ScreenTile, target::SplitTarget};
+
+/// Number of samples stored for each pixel
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SampleCount {
+	#[default]
+	One,
+	Two,
+	Four,
+	Eight,
+}
+impl SampleCount {
+	pub const fn count(self) -> usize {
+		match self {
+			SampleCount::One => 1,
+			SampleCount::Two => 2,
+			SampleCount::Four => 4,
+			SampleCount::Eight => 8,
+		}
+	}
+	/// Position of each sample, relative to the centre of the pixel
+	///
+	/// These are the standard sample patterns used by Direct3D and Vulkan, which are all multiples of 1/16th of a pixel
+	pub const fn offsets(self) -> &'static [(f32, f32)] {
+		const fn pattern<const N: usize>(offsets: [(i8, i8); N]) -> [(f32, f32); N] {
+			let mut pattern = [(0.0, 0.0); N];
+			let mut i = 0;
+			while i < N {
+				pattern[i] = (offsets[i].0 as f32 / 16.0, offsets[i].1 as f32 / 16.0);
+				i += 1;
+			}
+			pattern
+		}
+		const ONE: [(f32, f32); 1] = pattern([(0, 0)]);
+		const TWO: [(f32, f32); 2] = pattern([(4, 4), (-4, -4)]);
+		const FOUR: [(f32, f32); 4] = pattern([(-2, -6), (6, -2), (-6, 2), (2, 6)]);
+		const EIGHT: [(f32, f32); 8] = pattern([
+			(1, -3),
+			(-1, 3),
+			(5, 1),
+			(-3, -5),
+			(-5, 5),
+			(-7, -1),
+			(3, 7),
+			(7, -7),
+		]);
+		match self {
+			SampleCount::One => &ONE,
+			SampleCount::Two => &TWO,
+			SampleCount::Four => &FOUR,
+			SampleCount::Eight => &EIGHT,
+		}
+	}
+}
+
+/// [`Target`] that stores multiple colour and depth samples for each pixel
+///
+/// Both buffers hold `width * height * samples` values, with all of the samples for a pixel stored next to each other
+pub struct MultisampleScreen<'a, Pixel> {
+	pub sample_buffer: &'a mut [Pixel],
+	pub z_buffer: &'a mut [f32],
+	/// Stencil buffer, holding one value per pixel
+	pub stencil_buffer: Option<&'a mut [u8]>,
+	width: usize,
+	height: usize,
+	samples: SampleCount,
+	pub draw_colour: Pixel,
+}
+impl<'a, Pixel> MultisampleScreen<'a, Pixel>
+where
+	Pixel: Clone + Default,
+{
+	pub fn new(
+		sample_buffer: &'a mut [Pixel],
+		z_buffer: &'a mut [f32],
+		width: usize,
+		height: usize,
+		samples: SampleCount,
+	) -> Self {
+		Self {
+			sample_buffer,
+			z_buffer,
+			stencil_buffer: None,
+			width,
+			height,
+			samples,
+			draw_colour: Pixel::default(),
+		}
+	}
+	/// Adds a stencil buffer, which must hold `width * height` values
+	pub fn with_stencil(mut self, stencil_buffer: &'a mut [u8]) -> Self {
+		self.stencil_buffer = Some(stencil_buffer);
+		self
+	}
+	/// Index of the first sample of a pixel
+	#[inline]
+	fn index(&self, x: usize, y: usize) -> usize {
+		(y * self.width + x) * self.samples.count()
+	}
+	/// Indices of every sample of a pixel
+	#[inline]
+	fn samples(&self, x: usize, y: usize) -> Range<usize> {
+		let i = self.index(x, y);
+		i..i + self.samples.count()
+	}
+}
+impl<'a> MultisampleScreen<'a, Colour> {
+	/// Averages the samples of each pixel, and writes the result into `target`
+	///
+	/// The depth of each pixel is set to that of its nearest sample
+	pub fn resolve<T: Target<Item = Colour>>(&self, target: &mut T) {
+		let Some(area) = self.area().intersection(&target.area()) else {
+			return;
+		};
+		let count = self.samples.count() as u32;
+		for y in area.min_y..area.max_y {
+			for x in area.min_x..area.max_x {
+				let samples = self.samples(x, y);
+				let mut sum = [0_u32; 4];
+				for c in &self.sample_buffer[samples.clone()] {
+					sum[0] += c.red as u32;
+					sum[1] += c.green as u32;
+					sum[2] += c.blue as u32;
+					sum[3] += c.alpha as u32;
+				}
+				// Round to nearest
+				let [red, green, blue, alpha] = sum.map(|c| ((c + count / 2) / count) as u8);
+				target.set(x, y, Colour::new(red, green, blue, alpha));
+				let depth = self.z_buffer[samples]
+					.iter()
+					.fold(f32::INFINITY, |a, b| a.min(*b));
+				target.set_depth(x, y, depth);
+			}
+		}
+	}
+}
+impl<'a, Pixel> Target for MultisampleScreen<'a, Pixel>
+where
+	Pixel: Clone + Default,
+{
+	type Item = Pixel;
+
+	/// Sets every sample of the pixel
+	fn set(&mut self, x: usize, y: usize, value: Self::Item) {
+		let samples = self.samples(x, y);
+		self.sample_buffer[samples].fill(value);
+	}
+
+	/// Gets the first sample of the pixel
+	fn get(&self, x: usize, y: usize) -> Self::Item {
+		self.sample_buffer[self.index(x, y)].clone()
+	}
+
+	fn set_depth(&mut self, x: usize, y: usize, value: f32) {
+		let samples = self.samples(x, y);
+		self.z_buffer[samples].fill(value);
+	}
+
+	fn get_depth(&self, x: usize, y: usize) -> f32 {
+		self.z_buffer[self.index(x, y)]
+	}
+
+	fn clear(&mut self, fill: Self::Item) {
+		self.clear_depth();
+		self.clear_stencil(0);
+		self.sample_buffer.fill(fill);
+	}
+
+	fn clear_depth(&mut self) {
+		self.z_buffer.fill(f32::INFINITY);
+	}
+
+	fn area(&self) -> BoundingArea2D {
+		BoundingArea2D {
+			min_x: 0,
+			max_x: self.width,
+			min_y: 0,
+			max_y: self.height,
+		}
+	}
+
+	fn draw_colour(&self) -> Self::Item {
+		self.draw_colour.clone()
+	}
+	fn set_draw_colour(&mut self, value: Self::Item) {
+		self.draw_colour = value;
+	}
+
+	fn get_stencil(&self, x: usize, y: usize) -> Option<u8> {
+		let i = y * self.width + x;
+		self.stencil_buffer.as_ref().map(|stencil| stencil[i])
+	}
+	fn set_stencil(&mut self, x: usize, y: usize, value: u8) {
+		let i = y * self.width + x;
+		if let Some(stencil) = &mut self.stencil_buffer {
+			stencil[i] = value;
+		}
+	}
+	fn clear_stencil(&mut self, value: u8) {
+		if let Some(stencil) = &mut self.stencil_buffer {
+			stencil.fill(value);
+		}
+	}
+
+	fn sample_count(&self) -> SampleCount {
+		self.samples
+	}
+	fn set_sample(&mut self, x: usize, y: usize, sample: usize, value: Self::Item) {
+		let i = self.index(x, y) + sample;
+		self.sample_buffer[i] = value;
+	}
+	fn get_sample(&self, x: usize, y: usize, sample: usize) -> Self::Item {
+		self.sample_buffer[self.index(x, y) + sample].clone()
+	}
+	fn set_sample_depth(&mut self, x: usize, y: usize, sample: usize, value: f32) {
+		let i = self.index(x, y) + sample;
+		self.z_buffer[i] = value;
+	}
+	fn get_sample_depth(&self, x: usize, y: usize, sample: usize) -> f32 {
+		self.z_buffer[self.index(x, y) + sample]
+	}
+}
+
+#[cfg(feature = "std")]
+impl<'a, Pixel> SplitTarget for MultisampleScreen<'a, Pixel>
+where
+	Pixel: Clone + Default + Send,
+{
+	type Tile<'b>
+		= ScreenTile<'b, Pixel>
+	where
+		Self: 'b;
+
+	fn split(&mut self, area: &BoundingArea2D, rows: usize) -> Vec<Self::Tile<'_>> {
+		let Some(area) = area.intersection(&self.area()) else {
+			return Vec::new();
+		};
+		ScreenTile::split(
+			self.sample_buffer,
+			self.z_buffer,
+			self.stencil_buffer.as_deref_mut(),
+			self.width,
+			self.samples,
+			area,
+			rows,
+			&self.draw_colour,
+		)
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+	use rendy3d_maths::geometry::triangle::Triangle;
+
+	use super::*;
+	use crate::{
+		graphics::{
+			geometry::{clipping::NoClip, point::AbsoluteScreenCoordinate},
+			geometry_3d::point::Point,
+			pipeline::Pipeline,
+			screen::Screen,
+		},
+		render::render,
+	};
+
+	struct Fill;
+	impl Pipeline for Fill {
+		type VsOut = ();
+		type VsIn = ();
+		type Vertex = Point;
+		type Fragment = Colour;
+		type ClippingStrategy = NoClip;
+
+		fn vertex(&self, _index: usize, vertex: Point, _state: ()) -> (Point, ()) {
+			(vertex, ())
+		}
+		fn fragment(&self, _position: AbsoluteScreenCoordinate, _data: ()) -> Colour {
+			Colour::WHITE
+		}
+	}
+
+	/// Draws `triangles` in white on black with 4x MSAA, and returns the resolved image
+	fn draw(triangles: &[Triangle<Point>]) -> Vec<Colour> {
+		let (width, height) = (32, 32);
+		let samples = SampleCount::Four;
+		let mut sample_buffer = vec![Colour::BLACK; width * height * samples.count()];
+		let mut sample_depth = vec![f32::INFINITY; width * height * samples.count()];
+		let mut screen = MultisampleScreen::new(
+			&mut sample_buffer,
+			&mut sample_depth,
+			width,
+			height,
+			samples,
+		);
+		render(triangles.iter().cloned(), &mut Fill, &mut screen, ());
+
+		let mut frame = vec![Colour::BLACK; width * height];
+		let mut depth = vec![f32::INFINITY; width * height];
+		screen.resolve(&mut Screen::new(&mut frame, &mut depth, width, height));
+		frame
+	}
+
+	#[test]
+	fn edges_are_antialiased() {
+		let frame = draw(&[Triangle::new(
+			Point::new(-0.9, -0.8, 0.0),
+			Point::new(0.85, -0.7, 0.0),
+			Point::new(-0.6, 0.9, 0.0),
+		)]);
+		// Pixels along the edges are partially covered
+		assert!(
+			frame
+				.iter()
+				.any(|c| c.red != 0 && c.red != 255 && c.red == c.green)
+		);
+		assert!(frame.contains(&Colour::WHITE));
+	}
+
+	#[test]
+	fn shared_edges_are_fully_covered() {
+		// Two triangles that cover the whole screen, with a diagonal edge between them
+		let frame = draw(&[
+			Triangle::new(
+				Point::new(-1.0, -1.0, 0.0),
+				Point::new(1.0, -1.0, 0.0),
+				Point::new(-1.0, 1.0, 0.0),
+			),
+			Triangle::new(
+				Point::new(1.0, -1.0, 0.0),
+				Point::new(1.0, 1.0, 0.0),
+				Point::new(-1.0, 1.0, 0.0),
+			),
+		]);
+		assert!(frame.iter().all(|c| *c == Colour::WHITE));
+	}
+}
@@ -0,0 +1,150 @@
+//! Fixed-function alpha test, which discards fragments that are too transparent
+use crate::graphics::colour::Colour;
+
+/// Discards fragments with an alpha value below the threshold
+///
+/// Discarded fragments leave the target untouched, so they don't write depth or update the stencil buffer.
+/// This is used for cut-out geometry such as foliage and text, which would otherwise hide whatever is drawn behind it
+pub struct AlphaTest<F> {
+	/// Minimum alpha value of fragments that are drawn
+	pub threshold: u8,
+	alpha: fn(&F) -> u8,
+}
+impl<F: Alpha> AlphaTest<F> {
+	pub const fn new(threshold: u8) -> Self {
+		Self {
+			threshold,
+			alpha: F::alpha,
+		}
+	}
+}
+impl<F> AlphaTest<F> {
+	/// Whether `fragment` should be drawn
+	#[inline]
+	pub fn test(&self, fragment: &F) -> bool {
+		(self.alpha)(fragment) >= self.threshold
+	}
+}
+impl<F> Clone for AlphaTest<F> {
+	fn clone(&self) -> Self {
+		*self
+	}
+}
+impl<F> Copy for AlphaTest<F> {}
+
+/// Fragment types that have an alpha value
+pub trait Alpha {
+	fn alpha(&self) -> u8;
+}
+impl Alpha for Colour {
+	fn alpha(&self) -> u8 {
+		self.alpha
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+	use rendy3d_maths::geometry::triangle::Triangle;
+
+	use super::*;
+	use crate::{
+		graphics::{
+			geometry::{clipping::NoClip, point::AbsoluteScreenCoordinate},
+			geometry_3d::point::Point,
+			pipeline::{Pipeline, quad::PixelQuad},
+			screen::Screen,
+			target::Target,
+		},
+		render::render,
+	};
+
+	/// Discards the left quarter of the screen, and fades out towards the right with an alpha test
+	struct CutOut;
+	impl Pipeline for CutOut {
+		type VsOut = ();
+		type VsIn = ();
+		type Vertex = Point;
+		type Fragment = Colour;
+		type ClippingStrategy = NoClip;
+
+		fn vertex(&self, _index: usize, vertex: Point, _state: ()) -> (Point, ()) {
+			(vertex, ())
+		}
+		fn fragment(&self, position: AbsoluteScreenCoordinate, _data: ()) -> Colour {
+			Colour::new(255, 255, 255, 255 - position.x as u8 * 8)
+		}
+		fn fragment_quad(
+			&self,
+			position: AbsoluteScreenCoordinate,
+			_quad: &PixelQuad<()>,
+		) -> Option<Colour> {
+			(position.x >= 8).then(|| self.fragment(position, ()))
+		}
+		fn alpha_test(&self) -> Option<AlphaTest<Colour>> {
+			Some(AlphaTest::new(128))
+		}
+	}
+
+	/// Discards the left quarter of the screen, without an alpha test or any other settings
+	struct Discard;
+	impl Pipeline for Discard {
+		type VsOut = ();
+		type VsIn = ();
+		type Vertex = Point;
+		type Fragment = Colour;
+		type ClippingStrategy = NoClip;
+
+		fn vertex(&self, _index: usize, vertex: Point, _state: ()) -> (Point, ()) {
+			(vertex, ())
+		}
+		fn fragment(&self, _position: AbsoluteScreenCoordinate, _data: ()) -> Colour {
+			Colour::WHITE
+		}
+		fn fragment_quad(
+			&self,
+			position: AbsoluteScreenCoordinate,
+			_quad: &PixelQuad<()>,
+		) -> Option<Colour> {
+			(position.x >= 8).then(|| self.fragment(position, ()))
+		}
+	}
+
+	#[test]
+	fn discarded_fragments_do_not_write_depth() {
+		let (width, height) = (32, 32);
+		let mut frame = vec![Colour::BLACK; width * height];
+		let mut depth = vec![f32::INFINITY; width * height];
+		let mut screen = Screen::new(&mut frame, &mut depth, width, height);
+		let full = Triangle::new(
+			Point::new(-3.0, -3.0, 0.5),
+			Point::new(5.0, -3.0, 0.5),
+			Point::new(-3.0, 5.0, 0.5),
+		);
+		render([full], &mut Discard, &mut screen, ());
+		for x in 0..width {
+			let drawn = x >= 8;
+			assert_eq!(screen.get(x, 16) == Colour::WHITE, drawn);
+			assert_eq!(screen.get_depth(x, 16).is_finite(), drawn);
+		}
+	}
+
+	#[test]
+	fn discarded_fragments_are_not_drawn() {
+		let (width, height) = (32, 32);
+		let mut frame = vec![Colour::BLACK; width * height];
+		let mut depth = vec![f32::INFINITY; width * height];
+		let mut screen = Screen::new(&mut frame, &mut depth, width, height);
+		let full = Triangle::new(
+			Point::new(-3.0, -3.0, 0.5),
+			Point::new(5.0, -3.0, 0.5),
+			Point::new(-3.0, 5.0, 0.5),
+		);
+		render([full], &mut CutOut, &mut screen, ());
+		for x in 0..width {
+			// Only fragments that are neither discarded nor fail the alpha test are drawn, and write depth
+			let drawn = (8..16).contains(&x);
+			assert_eq!(screen.get(x, 16).red == 255, drawn);
+			assert_eq!(screen.get_depth(x, 16).is_finite(), drawn);
+		}
+	}
+}
@@ -4,3 +4,15 @@ pub enum BackFaceCulling {
 	CullAnticlockwise,
 	None,
 }
+impl BackFaceCulling {
+	/// Returns `true` if a triangle with the given signed area on the screen is discarded
+	///
+	/// The area is positive for triangles whose vertices are clockwise on the screen
+	pub(crate) const fn culls(&self, area: i64) -> bool {
+		match self {
+			Self::CullClockwise => area > 0,
+			Self::CullAnticlockwise => area < 0,
+			Self::None => false,
+		}
+	}
+}
@@ -0,0 +1,117 @@
+//! Blending of fragments with the pixels that are already in the target
+use crate::graphics::colour::Colour;
+
+/// Supported blend modes
+///
+/// The fixed-function modes are available as constants for any fragment type that implements [`Blendable`],
+/// e.g. `Blend::SOURCE_OVER`
+pub enum Blend<'a, F> {
+	/// Fragments overwrite the pixels in the target
+	Replace,
+	/// Called with the fragment and the pixel currently in the target, and the result is written to the target
+	Custom(&'a (dyn Fn(F, F) -> F + Sync)),
+}
+impl<F> Blend<'_, F> {
+	/// Combines the `source` fragment with the `destination` pixel
+	///
+	/// `destination` is only read from the target if it is actually needed
+	#[inline]
+	pub fn apply(&self, source: F, destination: impl FnOnce() -> F) -> F {
+		match self {
+			Blend::Replace => source,
+			Blend::Custom(f) => f(source, destination()),
+		}
+	}
+}
+impl<F: Blendable> Blend<'_, F> {
+	pub const SOURCE_OVER: Self = Blend::Custom(&F::source_over);
+	pub const PREMULTIPLIED: Self = Blend::Custom(&F::premultiplied);
+	pub const ADDITIVE: Self = Blend::Custom(&F::additive);
+	pub const MULTIPLY: Self = Blend::Custom(&F::multiply);
+	pub const MIN: Self = Blend::Custom(&F::min);
+	pub const MAX: Self = Blend::Custom(&F::max);
+}
+
+/// Types that support the fixed-function blend modes
+///
+/// In each function, `source` is the new fragment and `destination` is the pixel that is already in the target
+pub trait Blendable: Sized {
+	/// Standard alpha blending, where `source` has not been multiplied by its alpha
+	fn source_over(source: Self, destination: Self) -> Self;
+	/// Alpha blending, where `source` has already been multiplied by its alpha
+	fn premultiplied(source: Self, destination: Self) -> Self;
+	fn additive(source: Self, destination: Self) -> Self;
+	fn multiply(source: Self, destination: Self) -> Self;
+	fn min(source: Self, destination: Self) -> Self;
+	fn max(source: Self, destination: Self) -> Self;
+}
+
+/// Multiplies two channels, treating them as being in the range 0-1
+#[inline]
+const fn mul(a: u8, b: u8) -> u8 {
+	((a as u16 * b as u16 + 127) / 255) as u8
+}
+
+impl Colour {
+	#[inline]
+	fn zip(self, other: Self, f: impl Fn(u8, u8) -> u8) -> Self {
+		Colour::new(
+			f(self.red, other.red),
+			f(self.green, other.green),
+			f(self.blue, other.blue),
+			f(self.alpha, other.alpha),
+		)
+	}
+}
+impl Blendable for Colour {
+	fn source_over(source: Self, destination: Self) -> Self {
+		let alpha = source.alpha as u16;
+		let lerp = |s: u8, d: u8| ((s as u16 * alpha + d as u16 * (255 - alpha) + 127) / 255) as u8;
+		Colour::new(
+			lerp(source.red, destination.red),
+			lerp(source.green, destination.green),
+			lerp(source.blue, destination.blue),
+			source.alpha + mul(destination.alpha, 255 - source.alpha),
+		)
+	}
+	fn premultiplied(source: Self, destination: Self) -> Self {
+		let inverse_alpha = 255 - source.alpha;
+		source.zip(destination, |s, d| s.saturating_add(mul(d, inverse_alpha)))
+	}
+	fn additive(source: Self, destination: Self) -> Self {
+		source.zip(destination, u8::saturating_add)
+	}
+	fn multiply(source: Self, destination: Self) -> Self {
+		source.zip(destination, mul)
+	}
+	fn min(source: Self, destination: Self) -> Self {
+		source.zip(destination, u8::min)
+	}
+	fn max(source: Self, destination: Self) -> Self {
+		source.zip(destination, u8::max)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn colour_blend_modes() {
+		let source = Colour::new(200, 100, 0, 128);
+		let destination = Colour::new(0, 100, 255, 255);
+		let blend = |mode: Blend<Colour>| mode.apply(source, || destination);
+		assert_eq!(blend(Blend::Replace), source);
+		assert_eq!(blend(Blend::SOURCE_OVER), Colour::new(100, 100, 127, 255));
+		assert_eq!(blend(Blend::PREMULTIPLIED), Colour::new(200, 150, 127, 255));
+		assert_eq!(blend(Blend::ADDITIVE), Colour::new(200, 200, 255, 255));
+		assert_eq!(blend(Blend::MULTIPLY), Colour::new(0, 39, 0, 128));
+		assert_eq!(blend(Blend::MIN), Colour::new(0, 100, 0, 128));
+		assert_eq!(blend(Blend::MAX), Colour::new(200, 100, 255, 255));
+		let average = |s: Colour, d: Colour| s.zip(d, |s, d| ((s as u16 + d as u16) / 2) as u8);
+		assert_eq!(
+			blend(Blend::Custom(&average)),
+			Colour::new(100, 100, 127, 191)
+		);
+	}
+}
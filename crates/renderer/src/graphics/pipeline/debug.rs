@@ -0,0 +1,440 @@
+//! Diagnostic views, which show how a scene is being drawn instead of its final colours
+//!
+//! Any pipeline that outputs [`Colour`]s can be wrapped in a [`DebugView`], and then rendered as usual.
+//! Some modes are read back from the target's buffers once everything has been drawn, with [`DebugView::resolve`]
+use crate::graphics::{
+	colour::Colour,
+	geometry::{clipping::TriangleClipper, point::AbsoluteScreenCoordinate},
+	geometry_3d::point::Point,
+	interpolate::Static,
+	pipeline::{
+		Pipeline,
+		alpha_test::AlphaTest,
+		back_face_culling::BackFaceCulling,
+		blend::Blend,
+		depth::{DepthBias, DepthCompare, DepthState},
+		quad::PixelQuad,
+		stats::StatsCollector,
+		stencil::{StencilCompare, StencilOp, StencilState},
+	},
+	primitive::Primitive,
+	target::Target,
+};
+
+/// Colours of the overdraw heatmap
+///
+/// A pixel that has been drawn `n` times is given the colour at index `n - 1`, and the last colour is used for anything drawn more times than that
+pub const HEATMAP: [Colour; 8] = [
+	Colour::new(0, 0, 128, 255),
+	Colour::new(0, 0, 255, 255),
+	Colour::new(0, 255, 255, 255),
+	Colour::new(0, 255, 0, 255),
+	Colour::new(255, 255, 0, 255),
+	Colour::new(255, 128, 0, 255),
+	Colour::new(255, 0, 0, 255),
+	Colour::new(255, 255, 255, 255),
+];
+
+/// What a [`DebugView`] shows
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DebugMode {
+	/// The output of the wrapped pipeline, unchanged
+	#[default]
+	Off,
+	/// How many fragments were drawn to each pixel, including hidden ones, using the colours in [`HEATMAP`]
+	///
+	/// Fragments are counted in the target's stencil buffer, which must be cleared to 0 beforehand.
+	/// This takes over the stencil buffer: the wrapped pipeline's stencil state is ignored and whatever the buffer held is overwritten
+	Overdraw,
+	/// Distance from the camera of the depth in the depth buffer, from black at the near plane to white at the far plane
+	Depth,
+	/// Each triangle in a random colour
+	Triangles,
+	/// Edges of each triangle, drawn over the output of the wrapped pipeline
+	Wireframe,
+}
+impl DebugMode {
+	/// The mode after this one, wrapping around to [`DebugMode::Off`]
+	pub const fn next(self) -> Self {
+		match self {
+			DebugMode::Off => DebugMode::Overdraw,
+			DebugMode::Overdraw => DebugMode::Depth,
+			DebugMode::Depth => DebugMode::Triangles,
+			DebugMode::Triangles => DebugMode::Wireframe,
+			DebugMode::Wireframe => DebugMode::Off,
+		}
+	}
+}
+
+/// Wraps a pipeline, replacing its output with a diagnostic view
+///
+/// The vertex shader of the wrapped pipeline is always used, but its fragment shader only runs with [`DebugMode::Off`] and [`DebugMode::Wireframe`]
+pub struct DebugView<P> {
+	pub pipeline: P,
+	pub mode: DebugMode,
+	/// Distances to the near and far planes of the projection, used to linearise depth
+	pub near: f32,
+	pub far: f32,
+	/// Colour of the edges drawn with [`DebugMode::Wireframe`]
+	pub wireframe: Colour,
+}
+impl<P> DebugView<P> {
+	pub const fn new(pipeline: P, mode: DebugMode) -> Self {
+		Self {
+			pipeline,
+			mode,
+			near: 0.1,
+			far: 100.0,
+			wireframe: Colour::WHITE,
+		}
+	}
+	pub const fn with_depth_range(mut self, near: f32, far: f32) -> Self {
+		self.near = near;
+		self.far = far;
+		self
+	}
+	/// Distance of a depth between -1 and 1 from the camera, scaled so that the near plane is at 0 and the far plane is at 1
+	fn linear_depth(&self, z: f32) -> f32 {
+		let (near, far) = (self.near, self.far);
+		let distance = 2.0 * near * far / (far + near - z * (far - near));
+		((distance - near) / (far - near)).clamp(0.0, 1.0)
+	}
+	/// Replaces the pixels of `target` with the values of modes that are read back from its buffers
+	///
+	/// This must be called after drawing with [`DebugMode::Overdraw`] or [`DebugMode::Depth`], and does nothing in other modes.
+	/// Pixels that haven't been drawn to are left unchanged
+	///
+	/// # Panics
+	/// With [`DebugMode::Overdraw`], if the target doesn't have a stencil buffer to count fragments in
+	pub fn resolve<T: Target<Item = Colour>>(&self, target: &mut T) {
+		let area = target.scissor();
+		if self.mode == DebugMode::Overdraw && !area.is_empty() {
+			assert!(
+				target.get_stencil(area.min_x, area.min_y).is_some(),
+				"overdraw is counted in the stencil buffer, but the target doesn't have one"
+			);
+		}
+		for y in area.min_y..area.max_y {
+			for x in area.min_x..area.max_x {
+				let colour = match self.mode {
+					DebugMode::Overdraw => match target.get_stencil(x, y) {
+						Some(count @ 1..) => HEATMAP[(count as usize - 1).min(HEATMAP.len() - 1)],
+						_ => continue,
+					},
+					DebugMode::Depth => {
+						let depth = target.get_depth(x, y);
+						if !depth.is_finite() {
+							continue;
+						}
+						let z = target.depth_range().unmap(depth);
+						let value = (self.linear_depth(z) * 255.0) as u8;
+						Colour::new(value, value, value, 255)
+					}
+					_ => return,
+				};
+				target.set(x, y, colour);
+			}
+		}
+	}
+}
+impl<P> Pipeline for DebugView<P>
+where
+	P: Pipeline<Fragment = Colour>,
+	P::ClippingStrategy: TriangleClipper<(P::VsOut, Static<u32>)>,
+{
+	/// The vertex index is passed along with the wrapped pipeline's data, and replaced with an id of the primitive by the geometry stage
+	type VsOut = (P::VsOut, Static<u32>);
+	type VsIn = P::VsIn;
+	type Fragment = Colour;
+	type Vertex = P::Vertex;
+	type ClippingStrategy = P::ClippingStrategy;
+
+	fn vertex(
+		&self,
+		index: usize,
+		vertex: Self::Vertex,
+		state: Self::VsIn,
+	) -> (Point, Self::VsOut) {
+		let (position, data) = self.pipeline.vertex(index, vertex, state);
+		(position, (data, Static::new(index as u32)))
+	}
+	fn geometry(
+		&self,
+		primitive: Primitive<(Point, Self::VsOut)>,
+		mut emit: impl FnMut(Primitive<(Point, Self::VsOut)>),
+	) {
+		// Everything emitted by the wrapped pipeline is identified by the vertices of the primitive it came from
+		let id = primitive
+			.vertices()
+			.fold(0, |id, (_, (_, index))| hash(id ^ index.get()));
+		let id = Static::new(id);
+		let primitive = primitive.map_vertices(|(position, (data, _))| (position, data));
+		self.pipeline.geometry(primitive, |primitive| {
+			emit(primitive.map_vertices(|(position, data)| (position, (data, id))));
+		});
+	}
+	fn fragment(&self, position: AbsoluteScreenCoordinate, data: Self::VsOut) -> Colour {
+		self.pipeline.fragment(position, data.0)
+	}
+	fn fragment_quad(
+		&self,
+		position: AbsoluteScreenCoordinate,
+		quad: &PixelQuad<Self::VsOut>,
+	) -> Option<Colour> {
+		match self.mode {
+			// The colours of these are filled in from the target's buffers by `resolve`
+			DebugMode::Off | DebugMode::Depth => {
+				self.pipeline.fragment_quad(position, &quad.map(|v| &v.0))
+			}
+			DebugMode::Overdraw => Some(HEATMAP[0]),
+			DebugMode::Triangles => {
+				// The id is the same at every vertex, so it can be taken from the first one
+				let [(_, id), ..] = quad.vertices();
+				let [red, green, blue, _] = hash(id.get()).to_le_bytes();
+				Some(Colour::new(red, green, blue, 255))
+			}
+			DebugMode::Wireframe => {
+				let colour = self.pipeline.fragment_quad(position, &quad.map(|v| &v.0))?;
+				Some(if on_edge(quad) {
+					self.wireframe
+				} else {
+					colour
+				})
+			}
+		}
+	}
+	fn backface_culling() -> BackFaceCulling {
+		P::backface_culling()
+	}
+	fn alpha_test(&self) -> Option<AlphaTest<Colour>> {
+		self.pipeline.alpha_test()
+	}
+	fn early_depth_test(&self) -> bool {
+		self.pipeline.early_depth_test()
+	}
+	fn depth_state(&self) -> DepthState {
+		match self.mode {
+			DebugMode::Overdraw => {
+				DepthState::new(DepthCompare::Always, false, DepthBias::default())
+			}
+			_ => self.pipeline.depth_state(),
+		}
+	}
+	fn stencil_state(&self) -> Option<StencilState> {
+		match self.mode {
+			// Every fragment increments the count, whether or not it is hidden
+			DebugMode::Overdraw => Some(StencilState::new(StencilCompare::Always, 0).with_ops(
+				StencilOp::Increment,
+				StencilOp::Increment,
+				StencilOp::Increment,
+			)),
+			_ => self.pipeline.stencil_state(),
+		}
+	}
+	fn blend(&self) -> Blend<'_, Colour> {
+		match self.mode {
+			DebugMode::Off | DebugMode::Depth | DebugMode::Wireframe => self.pipeline.blend(),
+			DebugMode::Overdraw | DebugMode::Triangles => Blend::Replace,
+		}
+	}
+	fn stats(&self) -> Option<&StatsCollector> {
+		self.pipeline.stats()
+	}
+}
+
+/// Whether the pixel being shaded is within a pixel of any edge of its triangle
+fn on_edge<T>(quad: &PixelQuad<T>) -> bool {
+	let barycentric = quad.weights().map(|w| w.screen);
+	let lane = quad.lane();
+	let (row, column) = (lane & 0b10, lane & 0b01);
+	(0..3).any(|i| {
+		let ddx = barycentric[row | 0b01][i] - barycentric[row][i];
+		let ddy = barycentric[column | 0b10][i] - barycentric[column][i];
+		barycentric[lane][i] < ddx.abs() + ddy.abs()
+	})
+}
+
+/// Scrambles the bits of `value`, so that similar values give very different results
+const fn hash(mut value: u32) -> u32 {
+	value ^= value >> 16;
+	value = value.wrapping_mul(0x7feb352d);
+	value ^= value >> 15;
+	value = value.wrapping_mul(0x846ca68b);
+	value ^ (value >> 16)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+	use rendy3d_maths::geometry::triangle::Triangle;
+
+	use super::*;
+	use crate::{
+		graphics::{
+			geometry::clipping::{NoClip, SutherlandHodgman},
+			screen::Screen,
+			viewport::{DepthRange, Viewport},
+		},
+		render::render,
+	};
+	use rendy3d_maths::geometry::bounding_area::BoundingArea2D;
+
+	struct Flat;
+	impl Pipeline for Flat {
+		type VsOut = ();
+		type VsIn = ();
+		type Vertex = Point;
+		type Fragment = Colour;
+		type ClippingStrategy = NoClip;
+
+		fn vertex(&self, _index: usize, vertex: Point, _state: ()) -> (Point, ()) {
+			(vertex, ())
+		}
+		fn fragment(&self, _position: AbsoluteScreenCoordinate, _data: ()) -> Colour {
+			Colour::TEAL
+		}
+	}
+
+	fn draw(mode: DebugMode) -> Vec<Colour> {
+		let (width, height) = (32, 32);
+		let mut frame = vec![Colour::BLACK; width * height];
+		let mut depth = vec![f32::INFINITY; width * height];
+		let mut stencil = vec![0; width * height];
+		let mut screen =
+			Screen::new(&mut frame, &mut depth, width, height).with_stencil(&mut stencil);
+		// The left half of the screen is covered by two triangles, and the right half by one
+		let left = Triangle::new(
+			Point::new(-1.0, -1.0, 0.2),
+			Point::new(0.0, -1.0, 0.2),
+			Point::new(-1.0, 1.0, 0.2),
+		);
+		let full = Triangle::new(
+			Point::new(-3.0, -3.0, 0.8),
+			Point::new(5.0, -3.0, 0.8),
+			Point::new(-3.0, 5.0, 0.8),
+		);
+		let mut pipeline = DebugView::new(Flat, mode);
+		render([left, full], &mut pipeline, &mut screen, ());
+		pipeline.resolve(&mut screen);
+		frame
+	}
+
+	#[test]
+	fn debug_modes() {
+		let off = draw(DebugMode::Off);
+		assert!(off.iter().all(|c| *c == Colour::TEAL));
+
+		// Hidden fragments are counted too
+		let overdraw = draw(DebugMode::Overdraw);
+		assert_eq!(overdraw[16 * 32 + 2], HEATMAP[1]);
+		assert_eq!(overdraw[16 * 32 + 30], HEATMAP[0]);
+
+		let depth = draw(DebugMode::Depth);
+		let (near, far) = (depth[16 * 32 + 2], depth[16 * 32 + 30]);
+		assert!(near.red < far.red);
+		assert_eq!((near.red, near.green), (near.green, near.blue));
+
+		let triangles = draw(DebugMode::Triangles);
+		let (near, far) = (triangles[16 * 32 + 2], triangles[16 * 32 + 30]);
+		assert_ne!(near, far);
+		assert_eq!(triangles[8 * 32 + 3], near);
+		assert_eq!(triangles[24 * 32 + 28], far);
+
+		let wireframe = draw(DebugMode::Wireframe);
+		// The hypotenuse of the left triangle crosses the middle row just before x = 8
+		assert_eq!(wireframe[16 * 32 + 7], Colour::WHITE);
+		assert_eq!(wireframe[16 * 32 + 2], Colour::TEAL);
+		assert_eq!(wireframe[16 * 32 + 24], Colour::TEAL);
+	}
+
+	#[test]
+	fn overdraw_is_independent_of_clear_colour() {
+		let (width, height) = (32, 32);
+		// The clear colour is the second colour of the heatmap, which shouldn't be counted as a draw
+		let mut frame = vec![HEATMAP[1]; width * height];
+		let mut depth = vec![f32::INFINITY; width * height];
+		let mut stencil = vec![0; width * height];
+		let mut screen =
+			Screen::new(&mut frame, &mut depth, width, height).with_stencil(&mut stencil);
+		let full = Triangle::new(
+			Point::new(-3.0, -3.0, 0.5),
+			Point::new(5.0, -3.0, 0.5),
+			Point::new(-3.0, 5.0, 0.5),
+		);
+		let mut pipeline = DebugView::new(Flat, DebugMode::Overdraw);
+		render([full], &mut pipeline, &mut screen, ());
+		pipeline.resolve(&mut screen);
+		assert!(frame.iter().all(|c| *c == HEATMAP[0]));
+	}
+
+	#[test]
+	fn depth_is_read_through_the_depth_range() {
+		let (width, height) = (32, 32);
+		let full = Triangle::new(
+			Point::new(-3.0, -3.0, 0.5),
+			Point::new(5.0, -3.0, 0.5),
+			Point::new(-3.0, 5.0, 0.5),
+		);
+		let draw = |range| {
+			let mut frame = vec![Colour::BLACK; width * height];
+			let mut depth = vec![f32::INFINITY; width * height];
+			let mut screen = Screen::new(&mut frame, &mut depth, width, height);
+			let mut viewport = Viewport::new(BoundingArea2D::new(0, width, 0, height))
+				.unwrap()
+				.with_depth_range(range);
+			let mut pipeline = DebugView::new(Flat, DebugMode::Depth).with_depth_range(1.0, 4.0);
+			let mut target = viewport.target(&mut screen);
+			render([full.clone()], &mut pipeline, &mut target, ());
+			pipeline.resolve(&mut target);
+			frame[16 * width + 16]
+		};
+		// The same geometry looks the same, however its depth is stored
+		let full_range = draw(DepthRange::default());
+		assert_ne!(full_range, Colour::BLACK);
+		assert_eq!(draw(DepthRange::new(-1.0, -0.5)), full_range);
+	}
+
+	/// Clips triangles that pass through the near plane, splitting them into several triangles
+	struct Clipped;
+	impl Pipeline for Clipped {
+		type VsOut = ();
+		type VsIn = ();
+		type Vertex = Point;
+		type Fragment = Colour;
+		type ClippingStrategy = SutherlandHodgman;
+
+		fn vertex(&self, _index: usize, vertex: Point, _state: ()) -> (Point, ()) {
+			(vertex, ())
+		}
+		fn fragment(&self, _position: AbsoluteScreenCoordinate, _data: ()) -> Colour {
+			Colour::TEAL
+		}
+	}
+
+	#[test]
+	fn clipped_triangles_have_one_colour() {
+		let (width, height) = (32, 32);
+		let mut frame = vec![Colour::BLACK; width * height];
+		let mut depth = vec![f32::INFINITY; width * height];
+		let mut screen = Screen::new(&mut frame, &mut depth, width, height);
+		let triangle = Triangle::new(
+			Point::new(-0.9, -0.9, -3.0),
+			Point::new(0.9, -0.9, 0.3),
+			Point::new(0.1, 0.9, 0.7),
+		);
+		let mut pipeline = DebugView::new(Clipped, DebugMode::Triangles);
+		render([triangle], &mut pipeline, &mut screen, ());
+		let drawn: Vec<_> = frame.iter().filter(|c| **c != Colour::BLACK).collect();
+		assert!(drawn.len() > 10);
+		assert!(drawn.iter().all(|c| *c == drawn[0]));
+	}
+
+	#[test]
+	#[should_panic(expected = "stencil buffer")]
+	fn overdraw_needs_a_stencil_buffer() {
+		let (width, height) = (8, 8);
+		let mut frame = vec![Colour::BLACK; width * height];
+		let mut depth = vec![f32::INFINITY; width * height];
+		let mut screen = Screen::new(&mut frame, &mut depth, width, height);
+		DebugView::new(Flat, DebugMode::Overdraw).resolve(&mut screen);
+	}
+}
@@ -0,0 +1,118 @@
+//! Configuration of the depth test
+
+/// Depths closer together than this are treated as being equal by [`DepthCompare`]
+///
+/// This stops geometry from flickering when it is drawn over itself (or over coplanar geometry)
+pub const DEPTH_EPSILON: f32 = 0.00001;
+
+/// Comparison between the depth of a fragment and the depth already in the target
+///
+/// The fragment is only drawn if the comparison passes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DepthCompare {
+	Never,
+	Less,
+	LessEqual,
+	Equal,
+	NotEqual,
+	GreaterEqual,
+	Greater,
+	Always,
+}
+impl DepthCompare {
+	/// Compares the depth of a fragment (`z`) with the depth currently in the target (`buffered_z`)
+	#[inline]
+	pub fn test(self, z: f32, buffered_z: f32) -> bool {
+		let equal = f32::abs(z - buffered_z) < DEPTH_EPSILON;
+		match self {
+			DepthCompare::Never => false,
+			DepthCompare::Less => z < buffered_z && !equal,
+			DepthCompare::LessEqual => z < buffered_z || equal,
+			DepthCompare::Equal => equal,
+			DepthCompare::NotEqual => !equal,
+			DepthCompare::GreaterEqual => z > buffered_z || equal,
+			DepthCompare::Greater => z > buffered_z && !equal,
+			DepthCompare::Always => true,
+		}
+	}
+}
+
+/// Offset added to the depth of every fragment of a triangle
+///
+/// Used to stop decals, outlines and shadow maps from fighting with the surfaces that they lie on
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DepthBias {
+	/// Added to the depth of every fragment
+	pub constant: f32,
+	/// Multiplied by the triangle's maximum depth slope (change in depth per pixel) and added to the depth of every fragment
+	pub slope: f32,
+}
+impl DepthBias {
+	pub const fn new(constant: f32, slope: f32) -> Self {
+		Self { constant, slope }
+	}
+	/// Bias of a triangle whose depth changes by `dz_dx` and `dz_dy` per pixel
+	#[inline]
+	pub fn offset(&self, dz_dx: f32, dz_dy: f32) -> f32 {
+		self.constant + self.slope * dz_dx.abs().max(dz_dy.abs())
+	}
+}
+
+/// Depth test and depth write configuration of a pipeline
+///
+/// The default passes fragments that are nearer than (or at the same depth as) the target, and writes their depth
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DepthState {
+	pub compare: DepthCompare,
+	/// Whether fragments that pass the depth test write their depth to the target
+	pub write: bool,
+	pub bias: DepthBias,
+}
+impl DepthState {
+	pub const fn new(compare: DepthCompare, write: bool, bias: DepthBias) -> Self {
+		Self {
+			compare,
+			write,
+			bias,
+		}
+	}
+}
+impl Default for DepthState {
+	fn default() -> Self {
+		Self::new(DepthCompare::LessEqual, true, DepthBias::default())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn depth_compare() {
+		let compares = [
+			DepthCompare::Never,
+			DepthCompare::Less,
+			DepthCompare::LessEqual,
+			DepthCompare::Equal,
+			DepthCompare::NotEqual,
+			DepthCompare::GreaterEqual,
+			DepthCompare::Greater,
+			DepthCompare::Always,
+		];
+		// Results for a fragment in front of, at the same depth as, and behind the target
+		let expected = [
+			[false, false, false],
+			[true, false, false],
+			[true, true, false],
+			[false, true, false],
+			[true, false, true],
+			[false, true, true],
+			[false, false, true],
+			[true, true, true],
+		];
+		for (compare, expected) in compares.into_iter().zip(expected) {
+			let results = [0.4, 0.5 + DEPTH_EPSILON / 2.0, 0.6].map(|z| compare.test(z, 0.5));
+			assert_eq!(results, expected, "{compare:?}");
+		}
+	}
+}
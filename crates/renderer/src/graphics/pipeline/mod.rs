@@ -1,15 +1,31 @@
+pub mod alpha_test;
 pub mod back_face_culling;
+pub mod blend;
+pub mod debug;
+pub mod depth;
+pub mod quad;
+pub mod stats;
+pub mod stencil;
 
 use crate::graphics::{
 	geometry::{clipping::TriangleClipper, point::AbsoluteScreenCoordinate},
 	geometry_3d::point::Point,
 	interpolate::Interpolate,
-	pipeline::back_face_culling::BackFaceCulling,
+	pipeline::{
+		alpha_test::AlphaTest,
+		back_face_culling::BackFaceCulling,
+		blend::Blend,
+		depth::DepthState,
+		quad::PixelQuad,
+		stats::{StatsCollector, count},
+		stencil::StencilState,
+	},
+	primitive::Primitive,
 };
 
 /// Render pipeline trait
 ///
-/// This is where the vertex and fragment shaders are implemented. The back-face culling strategy is also selected here.
+/// This is where the vertex, geometry and fragment shaders are implemented. The back-face culling strategy, alpha test, depth and stencil state and blend mode are also selected here.
 pub trait Pipeline {
 	/// Output of vertex shader
 	type VsOut: Interpolate;
@@ -23,13 +39,146 @@ pub trait Pipeline {
 	type ClippingStrategy: TriangleClipper<Self::VsOut>;
 
 	/// Vertex shader
-	fn vertex(&self, index: usize, vertex: Self::Vertex, state: Self::VsIn) -> (Point, Self::VsOut);
+	fn vertex(&self, index: usize, vertex: Self::Vertex, state: Self::VsIn)
+	-> (Point, Self::VsOut);
+
+	/// Geometry shader, which runs once for each primitive after its vertices have been shaded
+	///
+	/// Any number of points, lines and triangles can be passed to `emit`. They are clipped and rasterized in the order that they are emitted.
+	/// This can be used to drop primitives, or to generate new ones (e.g. lines showing the normal of each face).
+	/// By default, the primitive is emitted unchanged
+	fn geometry(
+		&self,
+		primitive: Primitive<(Point, Self::VsOut)>,
+		mut emit: impl FnMut(Primitive<(Point, Self::VsOut)>),
+	) {
+		emit(primitive);
+	}
 
 	/// Fragment shader
 	fn fragment(&self, position: AbsoluteScreenCoordinate, data: Self::VsOut) -> Self::Fragment;
 
+	/// Fragment shader with access to screen-space derivatives
+	///
+	/// Pixels are shaded in 2x2 quads, so `quad` can be used to find how quickly any value derived from the interpolated data changes across the screen
+	/// (e.g. for selecting a mipmap level).
+	/// Returning `None` discards the fragment, leaving the target (including its depth and stencil buffers) untouched.
+	/// By default, this just calls [`Pipeline::fragment`]
+	fn fragment_quad(
+		&self,
+		position: AbsoluteScreenCoordinate,
+		quad: &PixelQuad<Self::VsOut>,
+	) -> Option<Self::Fragment> {
+		Some(self.fragment(position, quad.get()))
+	}
+
 	/// Back-face Culling Mode
 	fn backface_culling() -> BackFaceCulling {
 		BackFaceCulling::None
 	}
+
+	/// Alpha test, which discards fragments that are too transparent, or `None` to disable it
+	fn alpha_test(&self) -> Option<AlphaTest<Self::Fragment>> {
+		None
+	}
+
+	/// Whether the depth and stencil tests run before the fragment shader, so that pixels that are hidden aren't shaded
+	///
+	/// As depth and stencil are written before the fragment shader runs, this must only be enabled by pipelines that never discard fragments
+	/// (in [`Pipeline::fragment_quad`] or with an alpha test) and don't modify depth.
+	/// By default, it is disabled
+	fn early_depth_test(&self) -> bool {
+		false
+	}
+
+	/// Depth test, depth write and depth bias configuration
+	fn depth_state(&self) -> DepthState {
+		DepthState::default()
+	}
+
+	/// Stencil test configuration, or `None` to disable the stencil test
+	fn stencil_state(&self) -> Option<StencilState> {
+		None
+	}
+
+	/// Blend mode, used to combine fragments with the pixels already in the target
+	fn blend(&self) -> Blend<'_, Self::Fragment> {
+		Blend::Replace
+	}
+
+	/// Counters that are updated while rendering with this pipeline, or `None` to not gather any statistics
+	fn stats(&self) -> Option<&StatsCollector> {
+		None
+	}
+}
+
+/// Pipelines can be borrowed, e.g. to wrap a pipeline without taking ownership of it
+impl<P: Pipeline> Pipeline for &P {
+	type VsOut = P::VsOut;
+	type VsIn = P::VsIn;
+	type Fragment = P::Fragment;
+	type Vertex = P::Vertex;
+	type ClippingStrategy = P::ClippingStrategy;
+
+	fn vertex(
+		&self,
+		index: usize,
+		vertex: Self::Vertex,
+		state: Self::VsIn,
+	) -> (Point, Self::VsOut) {
+		(**self).vertex(index, vertex, state)
+	}
+	fn geometry(
+		&self,
+		primitive: Primitive<(Point, Self::VsOut)>,
+		emit: impl FnMut(Primitive<(Point, Self::VsOut)>),
+	) {
+		(**self).geometry(primitive, emit);
+	}
+	fn fragment(&self, position: AbsoluteScreenCoordinate, data: Self::VsOut) -> Self::Fragment {
+		(**self).fragment(position, data)
+	}
+	fn fragment_quad(
+		&self,
+		position: AbsoluteScreenCoordinate,
+		quad: &PixelQuad<Self::VsOut>,
+	) -> Option<Self::Fragment> {
+		(**self).fragment_quad(position, quad)
+	}
+	fn backface_culling() -> BackFaceCulling {
+		P::backface_culling()
+	}
+	fn alpha_test(&self) -> Option<AlphaTest<Self::Fragment>> {
+		(**self).alpha_test()
+	}
+	fn early_depth_test(&self) -> bool {
+		(**self).early_depth_test()
+	}
+	fn depth_state(&self) -> DepthState {
+		(**self).depth_state()
+	}
+	fn stencil_state(&self) -> Option<StencilState> {
+		(**self).stencil_state()
+	}
+	fn blend(&self) -> Blend<'_, Self::Fragment> {
+		(**self).blend()
+	}
+	fn stats(&self) -> Option<&StatsCollector> {
+		(**self).stats()
+	}
+}
+
+/// Runs the fragment shader and the alpha test for a pixel, returning `None` if the fragment is discarded
+#[inline]
+pub(crate) fn shade<P: Pipeline>(
+	pipeline: &P,
+	position: AbsoluteScreenCoordinate,
+	quad: &PixelQuad<P::VsOut>,
+) -> Option<P::Fragment> {
+	count(pipeline, |s| &s.fragments_shaded);
+	let fragment = pipeline.fragment_quad(position, quad)?;
+	match pipeline.alpha_test() {
+		Some(alpha_test) if !alpha_test.test(&fragment) => None,
+		_ => Some(fragment),
+	}
 }
@@ -0,0 +1,161 @@
+//! 2x2 blocks of pixels that are shaded together, so that screen-space derivatives can be calculated
+
+use crate::graphics::interpolate::{Interpolate, Weights};
+
+/// Weights of each pixel in a 2x2 block, relative to the triangle being rasterized
+///
+/// Lanes are ordered top-left, top-right, bottom-left, bottom-right.
+/// Pixels in the quad that aren't covered by the triangle still have (extrapolated) values, but aren't shaded.
+/// These "helper" pixels mean that derivatives are available at the edges of triangles.
+///
+/// Values are only interpolated when they are requested, so shaders that don't use derivatives don't pay for them
+pub struct PixelQuad<'a, T> {
+	vertices: [&'a T; 3],
+	weights: &'a [Weights; 4],
+	lane: usize,
+}
+impl<'a, T> PixelQuad<'a, T> {
+	pub const fn new(vertices: [&'a T; 3], weights: &'a [Weights; 4], lane: usize) -> Self {
+		Self {
+			vertices,
+			weights,
+			lane,
+		}
+	}
+	/// Index of the pixel currently being shaded
+	pub const fn lane(&self) -> usize {
+		self.lane
+	}
+	/// Data output by the vertex shader for each vertex of the triangle
+	pub const fn vertices(&self) -> [&'a T; 3] {
+		self.vertices
+	}
+	/// Weights of every pixel in the quad
+	pub const fn weights(&self) -> &'a [Weights; 4] {
+		self.weights
+	}
+	/// Quad over part of each vertex's data, e.g. one field of a tuple
+	pub fn map<U>(&self, f: impl Fn(&'a T) -> &'a U) -> PixelQuad<'a, U> {
+		PixelQuad::new(self.vertices.map(f), self.weights, self.lane)
+	}
+}
+impl<'a, T> PixelQuad<'a, T>
+where
+	T: Interpolate,
+{
+	/// Interpolated value for the pixel at `lane`
+	pub fn value(&self, lane: usize) -> T {
+		let [a, b, c] = self.vertices;
+		T::interpolate_screen(a, b, c, &self.weights[lane])
+	}
+	/// Interpolated value for the pixel currently being shaded
+	pub fn get(&self) -> T {
+		self.value(self.lane)
+	}
+	/// Difference between the values at lanes `a` and `b`
+	fn difference(&self, a: usize, b: usize) -> T {
+		T::difference(&self.value(a), &self.value(b))
+	}
+	/// Rate of change of the interpolated values in the x direction
+	///
+	/// See [`Interpolate::difference`] for how the change is measured
+	pub fn ddx(&self) -> T {
+		let row = self.lane & 0b10;
+		self.difference(row | 0b01, row)
+	}
+	/// Rate of change of the interpolated values in the y direction
+	///
+	/// See [`Interpolate::difference`] for how the change is measured
+	pub fn ddy(&self) -> T {
+		let column = self.lane & 0b01;
+		self.difference(column | 0b10, column)
+	}
+	/// Rate of change of `f` in the x direction, at the pixel currently being shaded
+	///
+	/// Calculated from the difference between the left and right pixels in the same row of the quad
+	pub fn ddx_of<U: Interpolate>(&self, f: impl Fn(T) -> U) -> U {
+		let row = self.lane & 0b10;
+		let left = f(self.value(row));
+		let right = f(self.value(row | 0b01));
+		U::difference(&right, &left)
+	}
+	/// Rate of change of `f` in the y direction, at the pixel currently being shaded
+	///
+	/// Calculated from the difference between the top and bottom pixels in the same column of the quad
+	pub fn ddy_of<U: Interpolate>(&self, f: impl Fn(T) -> U) -> U {
+		let column = self.lane & 0b01;
+		let top = f(self.value(column));
+		let bottom = f(self.value(column | 0b10));
+		U::difference(&bottom, &top)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	use crate::graphics::{colour::Colour, interpolate::Static};
+
+	#[test]
+	fn derivatives() {
+		let vertices = [1.0_f32, 3.0, 6.0];
+		// Values of 1, 3, 6 and 10
+		let weights = [
+			[1.0, 0.0, 0.0],
+			[0.0, 1.0, 0.0],
+			[0.0, 0.0, 1.0],
+			[1.0, 1.0, 1.0],
+		]
+		.map(Weights::linear);
+		let ddx = [2.0, 2.0, 4.0, 4.0];
+		let ddy = [5.0, 7.0, 5.0, 7.0];
+		let ddx_squared = [8.0, 8.0, 64.0, 64.0];
+		for lane in 0..4 {
+			let quad = PixelQuad::new(vertices.each_ref(), &weights, lane);
+			assert_eq!(quad.ddx(), ddx[lane]);
+			assert_eq!(quad.ddy(), ddy[lane]);
+			assert_eq!(quad.ddx_of(|v| v * v), ddx_squared[lane]);
+		}
+	}
+
+	#[test]
+	fn static_values_are_their_own_derivatives() {
+		let vertices = [Static::new(5_u32); 3];
+		let weights = [
+			[1.0, 0.0, 0.0],
+			[0.0, 1.0, 0.0],
+			[0.0, 0.0, 1.0],
+			[0.2, 0.3, 0.5],
+		]
+		.map(Weights::linear);
+		for lane in 0..4 {
+			let quad = PixelQuad::new(vertices.each_ref(), &weights, lane);
+			// A flat value has no meaningful derivative, so it is passed through unchanged
+			assert_eq!(quad.get().get(), 5);
+			assert_eq!(quad.ddx().get(), 5);
+			assert_eq!(quad.ddy().get(), 5);
+			assert_eq!(quad.ddx_of(|v| (v, 1.0_f32)).0.get(), 5);
+		}
+	}
+
+	#[test]
+	fn colour_derivatives_are_absolute() {
+		let vertices = [
+			Colour::new(10, 200, 0, 255),
+			Colour::new(30, 100, 0, 255),
+			Colour::new(10, 200, 40, 255),
+		];
+		let weights = [
+			[1.0, 0.0, 0.0],
+			[0.0, 1.0, 0.0],
+			[0.0, 0.0, 1.0],
+			[0.0, 1.0, 0.0],
+		]
+		.map(Weights::linear);
+		let quad = PixelQuad::new(vertices.each_ref(), &weights, 0);
+		// Red increases and green decreases to the right, but neither wraps around
+		assert_eq!(quad.ddx(), Colour::new(20, 100, 0, 0));
+		assert_eq!(quad.ddy(), Colour::new(0, 0, 40, 0));
+		assert_eq!(quad.ddx_of(|c| c), quad.ddx());
+	}
+}
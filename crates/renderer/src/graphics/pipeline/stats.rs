@@ -0,0 +1,208 @@
+//! Counters of the work done while rendering
+//!
+//! Statistics are only gathered for pipelines that return a [`StatsCollector`] from [`Pipeline::stats`], such as any pipeline wrapped in [`WithStats`].
+//! For every other pipeline, the counters are compiled out entirely.
+//! [`render_parallel`](crate::render::render_parallel) counts each tile separately and adds the totals once the tile is drawn, so worker threads don't contend for the counters
+//!
+//! On targets without atomic read-modify-write operations (e.g. `thumbv6m`), the counters are plain [`Cell`](core::cell::Cell)s, so they can only be used from one thread
+#[cfg(not(target_has_atomic = "ptr"))]
+use core::cell::Cell;
+#[cfg(target_has_atomic = "ptr")]
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::graphics::geometry_3d::point::Point;
+use crate::graphics::{
+	geometry::point::AbsoluteScreenCoordinate,
+	pipeline::{
+		Pipeline, alpha_test::AlphaTest, back_face_culling::BackFaceCulling, blend::Blend,
+		depth::DepthState, quad::PixelQuad, stencil::StencilState,
+	},
+	primitive::Primitive,
+};
+
+/// Single statistics counter, which can be shared between threads where the target supports atomics
+#[derive(Debug, Default)]
+pub struct Counter {
+	#[cfg(target_has_atomic = "ptr")]
+	value: AtomicUsize,
+	#[cfg(not(target_has_atomic = "ptr"))]
+	value: Cell<usize>,
+}
+#[cfg(target_has_atomic = "ptr")]
+impl Counter {
+	pub const fn new() -> Self {
+		Self {
+			value: AtomicUsize::new(0),
+		}
+	}
+	pub fn get(&self) -> usize {
+		self.value.load(Ordering::Relaxed)
+	}
+	/// Current value, resetting the counter to zero
+	pub fn take(&self) -> usize {
+		self.value.swap(0, Ordering::Relaxed)
+	}
+	pub fn add(&self, n: usize) {
+		self.value.fetch_add(n, Ordering::Relaxed);
+	}
+}
+#[cfg(not(target_has_atomic = "ptr"))]
+impl Counter {
+	pub const fn new() -> Self {
+		Self {
+			value: Cell::new(0),
+		}
+	}
+	pub fn get(&self) -> usize {
+		self.value.get()
+	}
+	/// Current value, resetting the counter to zero
+	pub fn take(&self) -> usize {
+		self.value.take()
+	}
+	pub fn add(&self, n: usize) {
+		self.value.set(self.value.get() + n);
+	}
+}
+
+macro_rules! render_stats {
+	($($(#[$doc:meta])* $name:ident,)+) => {
+		/// Number of primitives and pixels processed at each stage of rendering
+		#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+		pub struct RenderStats {
+			$($(#[$doc])* pub $name: usize,)+
+		}
+		impl core::ops::AddAssign for RenderStats {
+			fn add_assign(&mut self, other: Self) {
+				$(self.$name += other.$name;)+
+			}
+		}
+
+		/// Counters that are updated while rendering, which can be shared between threads
+		#[derive(Debug, Default)]
+		pub struct StatsCollector {
+			$($(#[$doc])* pub $name: Counter,)+
+		}
+		impl StatsCollector {
+			pub const fn new() -> Self {
+				Self {
+					$($name: Counter::new(),)+
+				}
+			}
+			/// Current value of every counter
+			pub fn get(&self) -> RenderStats {
+				RenderStats {
+					$($name: self.$name.get(),)+
+				}
+			}
+			/// Current value of every counter, resetting them all to zero
+			pub fn take(&self) -> RenderStats {
+				RenderStats {
+					$($name: self.$name.take(),)+
+				}
+			}
+			/// Adds `stats` to every counter
+			pub fn add(&self, stats: RenderStats) {
+				$(self.$name.add(stats.$name);)+
+			}
+		}
+	};
+}
+render_stats! {
+	/// Triangles that reached the clipping stage
+	triangles_submitted,
+	/// Triangles discarded for being entirely outside of the view frustum
+	triangles_rejected,
+	/// Triangles discarded by back-face culling
+	triangles_culled,
+	/// Triangles that were passed to the clipping strategy
+	triangles_clipped,
+	/// Triangles output by the clipping strategy
+	triangles_from_clipping,
+	/// Pixels that the depth and stencil tests were run for
+	pixels_tested,
+	/// Pixels that failed the depth or stencil test
+	pixels_failed,
+	/// Runs of the fragment shader
+	fragments_shaded,
+	/// Pixels written to the target
+	pixels_written,
+}
+
+/// Increments a counter, if the pipeline is gathering statistics
+#[inline]
+pub(crate) fn count<P: Pipeline>(pipeline: &P, counter: impl FnOnce(&StatsCollector) -> &Counter) {
+	if let Some(stats) = pipeline.stats() {
+		counter(stats).add(1);
+	}
+}
+
+/// Wraps a pipeline, gathering statistics whenever it is used to render
+///
+/// Any statistics gathered by the wrapped pipeline are replaced with those of the wrapper
+pub struct WithStats<P> {
+	pub pipeline: P,
+	pub stats: StatsCollector,
+}
+impl<P> WithStats<P> {
+	pub const fn new(pipeline: P) -> Self {
+		Self {
+			pipeline,
+			stats: StatsCollector::new(),
+		}
+	}
+}
+impl<P: Pipeline> Pipeline for WithStats<P> {
+	type VsOut = P::VsOut;
+	type VsIn = P::VsIn;
+	type Fragment = P::Fragment;
+	type Vertex = P::Vertex;
+	type ClippingStrategy = P::ClippingStrategy;
+
+	fn vertex(
+		&self,
+		index: usize,
+		vertex: Self::Vertex,
+		state: Self::VsIn,
+	) -> (Point, Self::VsOut) {
+		self.pipeline.vertex(index, vertex, state)
+	}
+	fn geometry(
+		&self,
+		primitive: Primitive<(Point, Self::VsOut)>,
+		emit: impl FnMut(Primitive<(Point, Self::VsOut)>),
+	) {
+		self.pipeline.geometry(primitive, emit);
+	}
+	fn fragment(&self, position: AbsoluteScreenCoordinate, data: Self::VsOut) -> Self::Fragment {
+		self.pipeline.fragment(position, data)
+	}
+	fn fragment_quad(
+		&self,
+		position: AbsoluteScreenCoordinate,
+		quad: &PixelQuad<Self::VsOut>,
+	) -> Option<Self::Fragment> {
+		self.pipeline.fragment_quad(position, quad)
+	}
+	fn backface_culling() -> BackFaceCulling {
+		P::backface_culling()
+	}
+	fn alpha_test(&self) -> Option<AlphaTest<Self::Fragment>> {
+		self.pipeline.alpha_test()
+	}
+	fn early_depth_test(&self) -> bool {
+		self.pipeline.early_depth_test()
+	}
+	fn depth_state(&self) -> DepthState {
+		self.pipeline.depth_state()
+	}
+	fn stencil_state(&self) -> Option<StencilState> {
+		self.pipeline.stencil_state()
+	}
+	fn blend(&self) -> Blend<'_, Self::Fragment> {
+		self.pipeline.blend()
+	}
+	fn stats(&self) -> Option<&StatsCollector> {
+		Some(&self.stats)
+	}
+}
@@ -0,0 +1,247 @@
+//! Configuration of the stencil test
+use crate::graphics::target::Target;
+
+/// Comparison between the reference value and the value in the stencil buffer
+///
+/// Both values are masked by [`StencilState::read_mask`] first. The fragment is only drawn if the comparison passes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StencilCompare {
+	Never,
+	Less,
+	LessEqual,
+	Equal,
+	NotEqual,
+	GreaterEqual,
+	Greater,
+	Always,
+}
+impl StencilCompare {
+	/// Compares `reference` with the value currently in the stencil buffer (`value`)
+	#[inline]
+	pub const fn test(self, reference: u8, value: u8) -> bool {
+		match self {
+			StencilCompare::Never => false,
+			StencilCompare::Less => reference < value,
+			StencilCompare::LessEqual => reference <= value,
+			StencilCompare::Equal => reference == value,
+			StencilCompare::NotEqual => reference != value,
+			StencilCompare::GreaterEqual => reference >= value,
+			StencilCompare::Greater => reference > value,
+			StencilCompare::Always => true,
+		}
+	}
+}
+
+/// Operation applied to the value in the stencil buffer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StencilOp {
+	Keep,
+	Zero,
+	/// Replace the value with the reference value
+	Replace,
+	/// Increment the value, clamping at 255
+	Increment,
+	/// Decrement the value, clamping at 0
+	Decrement,
+	IncrementWrap,
+	DecrementWrap,
+	/// Invert the bits of the value
+	Invert,
+}
+impl StencilOp {
+	#[inline]
+	pub const fn apply(self, reference: u8, value: u8) -> u8 {
+		match self {
+			StencilOp::Keep => value,
+			StencilOp::Zero => 0,
+			StencilOp::Replace => reference,
+			StencilOp::Increment => value.saturating_add(1),
+			StencilOp::Decrement => value.saturating_sub(1),
+			StencilOp::IncrementWrap => value.wrapping_add(1),
+			StencilOp::DecrementWrap => value.wrapping_sub(1),
+			StencilOp::Invert => !value,
+		}
+	}
+}
+
+/// Stencil test configuration of a pipeline
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StencilState {
+	pub compare: StencilCompare,
+	pub reference: u8,
+	/// Bits of the reference and stencil values that are compared
+	pub read_mask: u8,
+	/// Bits of the stencil value that can be changed by the stencil operations
+	pub write_mask: u8,
+	/// Applied when the stencil test fails
+	pub fail: StencilOp,
+	/// Applied when the stencil test passes, but the depth test fails
+	pub depth_fail: StencilOp,
+	/// Applied when both the stencil and depth tests pass
+	pub pass: StencilOp,
+}
+impl StencilState {
+	/// Stencil state that compares against `reference` without modifying the stencil buffer
+	pub const fn new(compare: StencilCompare, reference: u8) -> Self {
+		Self {
+			compare,
+			reference,
+			read_mask: 0xff,
+			write_mask: 0xff,
+			fail: StencilOp::Keep,
+			depth_fail: StencilOp::Keep,
+			pass: StencilOp::Keep,
+		}
+	}
+	pub const fn with_masks(mut self, read_mask: u8, write_mask: u8) -> Self {
+		self.read_mask = read_mask;
+		self.write_mask = write_mask;
+		self
+	}
+	pub const fn with_ops(
+		mut self,
+		fail: StencilOp,
+		depth_fail: StencilOp,
+		pass: StencilOp,
+	) -> Self {
+		self.fail = fail;
+		self.depth_fail = depth_fail;
+		self.pass = pass;
+		self
+	}
+	#[inline]
+	pub const fn test(&self, value: u8) -> bool {
+		self.compare
+			.test(self.reference & self.read_mask, value & self.read_mask)
+	}
+	/// New stencil value after applying `op` to `value`, only changing the bits in the write mask
+	#[inline]
+	pub const fn update(&self, value: u8, op: StencilOp) -> u8 {
+		let new = op.apply(self.reference, value);
+		(value & !self.write_mask) | (new & self.write_mask)
+	}
+}
+
+/// Runs the stencil test for a pixel, followed by `depth_test`, and updates the stencil buffer with the result
+///
+/// Returns whether both tests passed. `depth_test` is only run if the stencil test passes.
+/// If there is no stencil state, or the target doesn't have a stencil buffer, only `depth_test` is run
+#[inline]
+pub(crate) fn stencil_test<T: Target>(
+	target: &mut T,
+	stencil: Option<StencilState>,
+	x: usize,
+	y: usize,
+	depth_test: impl FnOnce(&mut T) -> bool,
+) -> bool {
+	let Some((stencil, value)) = stencil.zip(target.get_stencil(x, y)) else {
+		return depth_test(target);
+	};
+	if !stencil.test(value) {
+		target.set_stencil(x, y, stencil.update(value, stencil.fail));
+		return false;
+	}
+	let passed = depth_test(target);
+	let op = if passed {
+		stencil.pass
+	} else {
+		stencil.depth_fail
+	};
+	target.set_stencil(x, y, stencil.update(value, op));
+	passed
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+	use rendy3d_maths::geometry::triangle::Triangle;
+
+	use super::*;
+	use crate::{
+		graphics::{
+			colour::Colour,
+			geometry::{clipping::NoClip, point::AbsoluteScreenCoordinate},
+			geometry_3d::point::Point,
+			pipeline::Pipeline,
+			screen::Screen,
+		},
+		render::render,
+	};
+
+	/// Fills triangles with a colour, using a stencil state
+	struct Stencilled(Colour, StencilState);
+	impl Pipeline for Stencilled {
+		type VsOut = ();
+		type VsIn = ();
+		type Vertex = Point;
+		type Fragment = Colour;
+		type ClippingStrategy = NoClip;
+
+		fn vertex(&self, _index: usize, vertex: Point, _state: ()) -> (Point, ()) {
+			(vertex, ())
+		}
+		fn fragment(&self, _position: AbsoluteScreenCoordinate, _data: ()) -> Colour {
+			self.0
+		}
+		fn stencil_state(&self) -> Option<StencilState> {
+			Some(self.1)
+		}
+	}
+
+	#[test]
+	fn stencil_masks() {
+		let stencil = StencilState::new(StencilCompare::Equal, 0b0101)
+			.with_masks(0b0011, 0b1100)
+			.with_ops(StencilOp::Keep, StencilOp::Keep, StencilOp::Invert);
+		// Only the bottom two bits are compared
+		assert!(stencil.test(0b1101));
+		assert!(!stencil.test(0b0110));
+		// Only the top two bits are changed
+		assert_eq!(stencil.update(0b0110, stencil.pass), 0b1010);
+		assert_eq!(stencil.update(0b0110, StencilOp::Replace), 0b0110);
+	}
+
+	#[test]
+	fn stencil_masks_pixels() {
+		let (width, height) = (32, 32);
+		let mut frame = vec![Colour::BLACK; width * height];
+		let mut depth = vec![f32::INFINITY; width * height];
+		let mut stencil = vec![0; width * height];
+		let mut screen =
+			Screen::new(&mut frame, &mut depth, width, height).with_stencil(&mut stencil);
+		// Mark a small triangle in the stencil buffer
+		let small = Triangle::new(
+			Point::new(-0.5, -0.5, 0.5),
+			Point::new(0.5, -0.5, 0.5),
+			Point::new(0.0, 0.5, 0.5),
+		);
+		let replace = StencilState::new(StencilCompare::Always, 1).with_ops(
+			StencilOp::Keep,
+			StencilOp::Keep,
+			StencilOp::Replace,
+		);
+		render(
+			[small],
+			&mut Stencilled(Colour::RED, replace),
+			&mut screen,
+			(),
+		);
+		// Then draw over the whole screen, but only where the stencil buffer was marked
+		let full = Triangle::new(
+			Point::new(-3.0, -3.0, 0.0),
+			Point::new(5.0, -3.0, 0.0),
+			Point::new(-3.0, 5.0, 0.0),
+		);
+		let equal = StencilState::new(StencilCompare::Equal, 1);
+		render(
+			[full],
+			&mut Stencilled(Colour::BLUE, equal),
+			&mut screen,
+			(),
+		);
+		let stencil = screen.stencil_buffer.as_deref().unwrap();
+		for (colour, stencil) in screen.frame().iter().zip(stencil) {
+			assert_eq!(*colour, [Colour::BLACK, Colour::BLUE][*stencil as usize]);
+		}
+		assert!(stencil.contains(&1));
+	}
+}
@@ -0,0 +1,174 @@
+//! Separable blurs, which are run as a horizontal pass followed by a vertical pass
+//!
+//! The constructors return both passes, which can be chained with a [`PostProcessor`](crate::graphics::post_process::PostProcessor)
+use rendy3d_maths::traits::float::Float;
+
+use crate::graphics::{
+	colour::Colour,
+	post_process::{Input, Pass},
+};
+
+/// Largest radius supported by [`Blur`]
+pub const MAX_BLUR_RADIUS: usize = 16;
+
+/// Direction that a [`Blur`] pass averages pixels in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlurDirection {
+	Horizontal,
+	Vertical,
+}
+
+/// One direction of a separable blur
+///
+/// Each pixel is replaced with a weighted average of the pixels up to `radius` pixels away from it in one direction
+#[derive(Clone, Debug, PartialEq)]
+pub struct Blur {
+	/// Weight of the pixel at each distance from the centre, which sum to 1 over the whole kernel
+	weights: [f32; MAX_BLUR_RADIUS + 1],
+	radius: usize,
+	direction: BlurDirection,
+}
+impl Blur {
+	/// Blurs in the given direction, with a weight for each distance from the centre (starting at 0)
+	///
+	/// The weights are normalised, so only their relative size matters, and at most [`MAX_BLUR_RADIUS`] + 1 of them are used.
+	/// If there are no weights, or they don't add up to more than zero, the pass leaves the image unchanged
+	pub fn new(weights: &[f32], direction: BlurDirection) -> Self {
+		let len = weights.len().min(MAX_BLUR_RADIUS + 1);
+		let mut kernel = [0.0; MAX_BLUR_RADIUS + 1];
+		kernel[..len].copy_from_slice(&weights[..len]);
+		let radius = len.saturating_sub(1);
+		// Every weight apart from the centre is used on both sides
+		let total = kernel[0] + 2.0 * kernel[1..].iter().sum::<f32>();
+		if total <= 0.0 || total.is_nan() {
+			return Self::identity(direction);
+		}
+		for weight in &mut kernel {
+			*weight /= total;
+		}
+		Self {
+			weights: kernel,
+			radius,
+			direction,
+		}
+	}
+	/// Pass that only keeps the centre pixel, leaving the image unchanged
+	fn identity(direction: BlurDirection) -> Self {
+		let mut weights = [0.0; MAX_BLUR_RADIUS + 1];
+		weights[0] = 1.0;
+		Self {
+			weights,
+			radius: 0,
+			direction,
+		}
+	}
+	/// Horizontal and vertical passes of a box blur, which averages every pixel within `radius`
+	pub fn box_blur(radius: usize) -> [Self; 2] {
+		let weights = [1.0; MAX_BLUR_RADIUS + 1];
+		let weights = &weights[..=radius.min(MAX_BLUR_RADIUS)];
+		[
+			Self::new(weights, BlurDirection::Horizontal),
+			Self::new(weights, BlurDirection::Vertical),
+		]
+	}
+	/// Horizontal and vertical passes of a Gaussian blur with the standard deviation `sigma`, in pixels
+	///
+	/// The kernel covers 3 standard deviations either side of the centre, up to [`MAX_BLUR_RADIUS`].
+	/// If `sigma` isn't positive, the passes leave the image unchanged
+	pub fn gaussian(sigma: f32) -> [Self; 2] {
+		if sigma <= 0.0 || sigma.is_nan() {
+			return [
+				Self::identity(BlurDirection::Horizontal),
+				Self::identity(BlurDirection::Vertical),
+			];
+		}
+		let radius = ((sigma * 3.0) as usize).clamp(1, MAX_BLUR_RADIUS);
+		let mut weights = [0.0; MAX_BLUR_RADIUS + 1];
+		for (i, weight) in weights.iter_mut().enumerate().take(radius + 1) {
+			let i = i as f32;
+			*weight = Float::exp(-(i * i) / (2.0 * sigma * sigma));
+		}
+		let weights = &weights[..=radius];
+		[
+			Self::new(weights, BlurDirection::Horizontal),
+			Self::new(weights, BlurDirection::Vertical),
+		]
+	}
+}
+impl Pass<Colour> for Blur {
+	fn apply(&self, input: &Input<Colour>, x: usize, y: usize) -> Colour {
+		let mut total = [0.0; 4];
+		for offset in -(self.radius as isize)..=self.radius as isize {
+			let weight = self.weights[offset.unsigned_abs()];
+			let colour = match self.direction {
+				BlurDirection::Horizontal => input.neighbour(x, y, offset, 0),
+				BlurDirection::Vertical => input.neighbour(x, y, 0, offset),
+			};
+			let channels = [colour.red, colour.green, colour.blue, colour.alpha];
+			for (total, channel) in total.iter_mut().zip(channels) {
+				*total += channel as f32 * weight;
+			}
+		}
+		let [red, green, blue, alpha] = total.map(|c| (c + 0.5).clamp(0.0, 255.0) as u8);
+		Colour::new(red, green, blue, alpha)
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+	use super::*;
+	use crate::graphics::post_process::PostProcessor;
+
+	#[test]
+	fn blurs_spread_pixels_out() {
+		let (width, height) = (9, 9);
+		let mut image = vec![Colour::BLACK; width * height];
+		let mut scratch = image.clone();
+		// Single white pixel in the middle of the image
+		image[4 * width + 4] = Colour::new(255, 255, 255, 255);
+		let brightness = |image: &[Colour]| image.iter().map(|c| c.red as u32).sum::<u32>();
+
+		let mut box_blurred = image.clone();
+		let mut post = PostProcessor::new(&mut box_blurred, &mut scratch, width, height);
+		let [horizontal, vertical] = Blur::box_blur(1);
+		post.apply(&horizontal).apply(&vertical);
+		// Spread evenly over a 3x3 square
+		for y in 0..height {
+			for x in 0..width {
+				let inside = (3..6).contains(&x) && (3..6).contains(&y);
+				assert_eq!(post.image()[y * width + x].red, if inside { 28 } else { 0 });
+			}
+		}
+
+		let mut scratch = image.clone();
+		let mut post = PostProcessor::new(&mut image, &mut scratch, width, height);
+		let [horizontal, vertical] = Blur::gaussian(1.0);
+		post.apply(&horizontal).apply(&vertical);
+		let blurred = post.image();
+		// Brightness falls off with distance, and is roughly conserved
+		assert!(blurred[4 * width + 4].red > blurred[4 * width + 5].red);
+		assert!(blurred[4 * width + 5].red > blurred[5 * width + 5].red);
+		assert!(blurred[5 * width + 5].red > blurred[4 * width + 7].red);
+		assert!(brightness(blurred).abs_diff(255) < 10);
+	}
+
+	#[test]
+	fn degenerate_blurs_leave_image_unchanged() {
+		let (width, height) = (5, 4);
+		let image: Vec<_> = (0..width * height)
+			.map(|i| Colour::new(i as u8 * 10, 255 - i as u8, 0, 255))
+			.collect();
+		let input = Input::new(&image, width, height);
+		let empty = Blur::new(&[], BlurDirection::Horizontal);
+		let zero = Blur::new(&[0.0, 0.0], BlurDirection::Vertical);
+		let [horizontal, vertical] = Blur::gaussian(0.0);
+		let [negative, _] = Blur::gaussian(-1.0);
+		for blur in [empty, zero, horizontal, vertical, negative] {
+			for y in 0..height {
+				for x in 0..width {
+					assert_eq!(blur.apply(&input, x, y), image[y * width + x]);
+				}
+			}
+		}
+	}
+}
@@ -0,0 +1,142 @@
+//! Passes that adjust the colour of each pixel
+use rendy3d_maths::traits::float::Float;
+
+use crate::graphics::{
+	colour::Colour,
+	post_process::{Input, Pass},
+};
+
+/// Applies `f` to the red, green and blue channels of a colour, as values between 0 and 1
+#[inline]
+fn map_channels(colour: Colour, f: impl Fn(f32) -> f32) -> Colour {
+	let channel = |c: u8| (f(c as f32 / 255.0) * 255.0 + 0.5).clamp(0.0, 255.0) as u8;
+	Colour::new(
+		channel(colour.red),
+		channel(colour.green),
+		channel(colour.blue),
+		colour.alpha,
+	)
+}
+
+/// Reinhard tone mapping, which brightens dark colours while compressing bright ones
+///
+/// White is left unchanged. Higher exposures brighten the image more, and exposures that aren't positive leave the image unchanged
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ToneMap {
+	pub exposure: f32,
+}
+impl ToneMap {
+	pub const fn new(exposure: f32) -> Self {
+		Self { exposure }
+	}
+}
+impl Pass<Colour> for ToneMap {
+	fn apply(&self, input: &Input<Colour>, x: usize, y: usize) -> Colour {
+		let exposure = self.exposure;
+		if exposure <= 0.0 || exposure.is_nan() {
+			return input.get(x, y);
+		}
+		// Scaled so that an input of 1 still maps to 1
+		let scale = (1.0 + exposure) / exposure;
+		map_channels(input.get(x, y), |c| {
+			let c = c * exposure;
+			c / (1.0 + c) * scale
+		})
+	}
+}
+
+/// Gamma correction, which raises each channel to the power of `1 / gamma`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gamma {
+	/// Corrected value of each possible channel value
+	table: [u8; 256],
+}
+impl Gamma {
+	pub fn new(gamma: f32) -> Self {
+		let mut table = [0; 256];
+		for (i, value) in table.iter_mut().enumerate() {
+			let c = Float::powf(i as f32 / 255.0, 1.0 / gamma);
+			*value = (c * 255.0 + 0.5).clamp(0.0, 255.0) as u8;
+		}
+		Self { table }
+	}
+}
+impl Pass<Colour> for Gamma {
+	fn apply(&self, input: &Input<Colour>, x: usize, y: usize) -> Colour {
+		let colour = input.get(x, y);
+		let channel = |c: u8| self.table[c as usize];
+		Colour::new(
+			channel(colour.red),
+			channel(colour.green),
+			channel(colour.blue),
+			colour.alpha,
+		)
+	}
+}
+
+/// Darkens the image towards its corners
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vignette {
+	/// How much the corners are darkened, where 1 makes them black
+	pub strength: f32,
+	/// Distance from the centre at which the image starts to darken, where the corners are at a distance of 1
+	pub radius: f32,
+}
+impl Vignette {
+	pub const fn new(strength: f32, radius: f32) -> Self {
+		Self { strength, radius }
+	}
+}
+impl Pass<Colour> for Vignette {
+	fn apply(&self, input: &Input<Colour>, x: usize, y: usize) -> Colour {
+		let half_width = input.width() as f32 / 2.0;
+		let half_height = input.height() as f32 / 2.0;
+		let dx = (x as f32 + 0.5 - half_width) / half_width;
+		let dy = (y as f32 + 0.5 - half_height) / half_height;
+		let distance = Float::sqrt((dx * dx + dy * dy) / 2.0);
+		let t = ((distance - self.radius) / (1.0 - self.radius)).clamp(0.0, 1.0);
+		// Smoothstep, so that there is no visible edge where the darkening starts
+		let darkening = t * t * (3.0 - 2.0 * t) * self.strength;
+		map_channels(input.get(x, y), |c| c * (1.0 - darkening))
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+	use super::*;
+
+	#[test]
+	fn colour_passes() {
+		let colours = [
+			Colour::BLACK,
+			Colour::new(64, 128, 255, 255),
+			Colour::new(200, 200, 200, 100),
+			Colour::WHITE,
+		];
+		let input = Input::new(&colours, 2, 2);
+		let tone_map = ToneMap::new(2.0);
+		assert_eq!(tone_map.apply(&input, 0, 0), Colour::BLACK);
+		assert_eq!(
+			tone_map.apply(&input, 1, 0),
+			Colour::new(128, 192, 255, 255)
+		);
+		assert_eq!(tone_map.apply(&input, 1, 1), Colour::WHITE);
+		for exposure in [0.0, -1.0] {
+			let tone_map = ToneMap::new(exposure);
+			assert_eq!(tone_map.apply(&input, 1, 0), colours[1]);
+			assert_eq!(tone_map.apply(&input, 0, 1), colours[2]);
+		}
+
+		let gamma = Gamma::new(2.2);
+		assert_eq!(gamma.apply(&input, 1, 0), Colour::new(136, 186, 255, 255));
+		// Alpha is left unchanged
+		assert_eq!(gamma.apply(&input, 0, 1), Colour::new(228, 228, 228, 100));
+
+		// In a 2x2 image, every pixel is the same distance from the centre
+		let vignette = Vignette::new(1.0, 0.0);
+		assert_eq!(
+			vignette.apply(&input, 1, 1),
+			Colour::new(128, 128, 128, 255)
+		);
+	}
+}
@@ -0,0 +1,245 @@
+//! Fast approximate anti-aliasing (FXAA)
+//!
+//! Edges are found from the brightness (luma) of neighbouring pixels, and optionally from the depth buffer.
+//! Pixels on an edge are blended with their neighbour across the edge, by an amount that depends on where they are along the edge.
+//! This is much cheaper than multisampling, at the cost of slightly softening the image
+use crate::graphics::{
+	colour::Colour,
+	post_process::{Input, Pass},
+	screen::Screen,
+	target::Target,
+};
+
+/// Furthest distance, in pixels, that each end of an edge is searched for
+const SEARCH_STEPS: isize = 12;
+
+/// Settings of the FXAA pass
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fxaa {
+	/// Minimum contrast of an edge, relative to the brightest pixel around it. Lower values smooth more edges, but are slower
+	pub edge_threshold: f32,
+	/// Minimum contrast of an edge, which stops noise in dark areas from being smoothed
+	pub edge_threshold_min: f32,
+	/// How much aliasing within a single pixel (e.g. thin lines) is smoothed, from 0 to 1
+	pub subpixel: f32,
+	/// Difference in depth at which there is always an edge, even if the colours on either side are similar.
+	/// `None` only uses luma to find edges
+	pub depth_threshold: Option<f32>,
+}
+impl Fxaa {
+	pub const fn new() -> Self {
+		Self {
+			edge_threshold: 0.125,
+			edge_threshold_min: 0.0312,
+			subpixel: 0.75,
+			depth_threshold: None,
+		}
+	}
+	pub const fn with_depth_threshold(mut self, threshold: f32) -> Self {
+		self.depth_threshold = Some(threshold);
+		self
+	}
+	/// Whether the depth of any of the pixel's direct neighbours is too different from its own
+	fn depth_edge(&self, input: &Input<Colour>, x: usize, y: usize) -> bool {
+		let Some(threshold) = self.depth_threshold else {
+			return false;
+		};
+		let depth = input.depth(x, y);
+		[(0, -1), (0, 1), (-1, 0), (1, 0)]
+			.into_iter()
+			.any(|(dx, dy)| (input.neighbour_depth(x, y, dx, dy) - depth).abs() > threshold)
+	}
+}
+impl Default for Fxaa {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+impl Pass<Colour> for Fxaa {
+	fn apply(&self, input: &Input<Colour>, x: usize, y: usize) -> Colour {
+		let luma_at = |dx, dy| luma(input.neighbour(x, y, dx, dy));
+		let centre = input.get(x, y);
+		let l = luma(centre);
+		let [n, s, w, e] = [(0, -1), (0, 1), (-1, 0), (1, 0)].map(|(dx, dy)| luma_at(dx, dy));
+		let max = l.max(n).max(s).max(w).max(e);
+		let min = l.min(n).min(s).min(w).min(e);
+		let range = max - min;
+		if range < self.edge_threshold_min.max(max * self.edge_threshold)
+			&& !self.depth_edge(input, x, y)
+		{
+			return centre;
+		}
+		let [nw, ne, sw, se] = [(-1, -1), (1, -1), (-1, 1), (1, 1)].map(|(dx, dy)| luma_at(dx, dy));
+
+		// Edges run in the direction that luma changes least in
+		let horizontal =
+			(n + s - 2.0 * l).abs() * 2.0 + (nw + sw - 2.0 * w).abs() + (ne + se - 2.0 * e).abs();
+		let vertical =
+			(w + e - 2.0 * l).abs() * 2.0 + (nw + ne - 2.0 * n).abs() + (sw + se - 2.0 * s).abs();
+		let is_horizontal = horizontal >= vertical;
+		// The neighbour across the edge is whichever side is most different to this pixel
+		let (before, after) = if is_horizontal { (n, s) } else { (w, e) };
+		let (sign, opposite) = if (before - l).abs() >= (after - l).abs() {
+			(-1, before)
+		} else {
+			(1, after)
+		};
+		let gradient = (opposite - l).abs() * 0.25;
+		let edge_luma = (l + opposite) * 0.5;
+		let (across, along) = if is_horizontal {
+			((0, sign), (1, 0))
+		} else {
+			((sign, 0), (0, 1))
+		};
+
+		// Search along the edge in both directions, until the luma either side of it changes
+		let end = |direction: isize| {
+			let mut last = edge_luma;
+			for i in 1..=SEARCH_STEPS {
+				let (dx, dy) = (along.0 * i * direction, along.1 * i * direction);
+				last = (luma_at(dx, dy) + luma_at(dx + across.0, dy + across.1)) * 0.5;
+				if (last - edge_luma).abs() >= gradient {
+					return (i as f32 - 0.5, last);
+				}
+			}
+			(SEARCH_STEPS as f32 + 0.5, last)
+		};
+		let (negative, negative_luma) = end(-1);
+		let (positive, positive_luma) = end(1);
+		let (distance, end_luma) = if negative < positive {
+			(negative, negative_luma)
+		} else {
+			(positive, positive_luma)
+		};
+		// Only blend if the nearest end of the edge goes the opposite way to this pixel, otherwise this pixel is already on the right side
+		let edge_offset = if (end_luma < edge_luma) != (l < edge_luma) {
+			0.5 - distance / (negative + positive)
+		} else {
+			0.0
+		};
+
+		// Pixels that stand out from all of their neighbours (e.g. on thin lines) are blended too
+		let average = ((n + s + w + e) * 2.0 + nw + ne + sw + se) / 12.0;
+		let subpixel = if range > 0.0 {
+			let contrast = ((average - l).abs() / range).clamp(0.0, 1.0);
+			let smooth = (3.0 - 2.0 * contrast) * contrast * contrast;
+			smooth * smooth * self.subpixel
+		} else {
+			0.0
+		};
+
+		let offset = edge_offset.max(subpixel);
+		let neighbour = input.neighbour(x, y, across.0, across.1);
+		lerp(centre, neighbour, offset)
+	}
+}
+
+/// Runs FXAA over the whole screen
+///
+/// `scratch` must hold as many pixels as the screen, and is used to keep a copy of the image before anti-aliasing.
+/// If the settings have a depth threshold, the screen's depth buffer is used to find edges
+pub fn fxaa(screen: &mut Screen<Colour>, scratch: &mut [Colour], settings: &Fxaa) {
+	let area = screen.area();
+	scratch.copy_from_slice(screen.frame_buffer);
+	let mut input = Input::new(scratch, area.width(), area.height());
+	if settings.depth_threshold.is_some() {
+		input = input.with_depth(screen.z_buffer);
+	}
+	for y in 0..area.height() {
+		for x in 0..area.width() {
+			screen.frame_buffer[y * area.width() + x] = settings.apply(&input, x, y);
+		}
+	}
+}
+
+/// Perceived brightness of a colour, from 0 to 1
+#[inline]
+fn luma(colour: Colour) -> f32 {
+	(colour.red as f32 * 0.299 + colour.green as f32 * 0.587 + colour.blue as f32 * 0.114) / 255.0
+}
+
+#[inline]
+fn lerp(a: Colour, b: Colour, t: f32) -> Colour {
+	let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t + 0.5) as u8;
+	Colour::new(
+		channel(a.red, b.red),
+		channel(a.green, b.green),
+		channel(a.blue, b.blue),
+		channel(a.alpha, b.alpha),
+	)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+	use super::*;
+
+	const SIZE: usize = 16;
+	const DARK: Colour = Colour::new(0, 0, 0, 255);
+	const LIGHT: Colour = Colour::new(255, 255, 255, 255);
+
+	/// Light image with a dark staircase in the bottom-left, whose steps are 4 pixels wide and 1 pixel tall
+	fn staircase() -> Vec<Colour> {
+		(0..SIZE * SIZE)
+			.map(|i| {
+				let (x, y) = (i % SIZE, i / SIZE);
+				if x / 4 < y.saturating_sub(8) {
+					DARK
+				} else {
+					LIGHT
+				}
+			})
+			.collect()
+	}
+
+	#[test]
+	fn edges_are_smoothed() {
+		let image = staircase();
+		let mut frame = image.clone();
+		let mut depth = vec![0.5; SIZE * SIZE];
+		let mut scratch = vec![Colour::BLACK; SIZE * SIZE];
+		let mut screen = Screen::new(&mut frame, &mut depth, SIZE, SIZE);
+		fxaa(&mut screen, &mut scratch, &Fxaa::new());
+		// Pixels away from the edge are untouched
+		assert_eq!(frame[..8 * SIZE], image[..8 * SIZE]);
+		assert_eq!(frame[15 * SIZE], DARK);
+		// Pixels along each step are blended, most strongly near the ends of the step
+		let changed = frame.iter().zip(&image).filter(|(a, b)| a != b).count();
+		assert!(changed > 0);
+		let row = |y: usize| (0..8).map(|x| frame[y * SIZE + x].red).collect::<Vec<_>>();
+		assert!(
+			row(10)[4..].iter().any(|c| *c != 255 && *c != 0),
+			"{:?}",
+			row(10)
+		);
+	}
+
+	#[test]
+	fn depth_finds_edges_between_similar_colours() {
+		// The same staircase, but with colours that are too similar to be an edge on their own
+		let image = staircase()
+			.into_iter()
+			.map(|c| {
+				if c == DARK {
+					Colour::new(100, 100, 100, 255)
+				} else {
+					Colour::new(110, 110, 110, 255)
+				}
+			})
+			.collect::<Vec<_>>();
+		let depth = image
+			.iter()
+			.map(|c| if c.red == 100 { 0.2 } else { 0.8 })
+			.collect::<Vec<_>>();
+		let input = Input::new(&image, SIZE, SIZE).with_depth(&depth);
+		let output = |settings: Fxaa| {
+			(0..SIZE * SIZE)
+				.map(|i| settings.apply(&input, i % SIZE, i / SIZE))
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(output(Fxaa::new()), image);
+		let with_depth = output(Fxaa::new().with_depth_threshold(0.1));
+		assert_ne!(with_depth, image);
+		// Pixels that aren't next to the depth edge are untouched
+		assert_eq!(with_depth[..8 * SIZE], image[..8 * SIZE]);
+	}
+}
@@ -0,0 +1,229 @@
+//! Full-screen passes that run over a finished image
+//!
+//! A [`Pass`] calculates each pixel of its output from an [`Input`], which holds a colour buffer and (optionally) a depth buffer.
+//! Passes can be run straight into any [`Target`] with [`run`], or chained together with a [`PostProcessor`],
+//! which ping-pongs between two buffers so that each pass reads the output of the one before it
+pub mod blur;
+pub mod colour;
+pub mod fxaa;
+pub mod ssao;
+
+use core::mem;
+
+use rendy3d_maths::geometry::bounding_area::BoundingArea2D;
+
+use crate::graphics::target::Target;
+
+/// Buffers that a [`Pass`] reads from
+///
+/// Every buffer must hold `width * height` values
+pub struct Input<'a, T> {
+	pub colour: &'a [T],
+	pub depth: Option<&'a [f32]>,
+	width: usize,
+	height: usize,
+}
+impl<'a, T> Input<'a, T> {
+	pub const fn new(colour: &'a [T], width: usize, height: usize) -> Self {
+		Self {
+			colour,
+			depth: None,
+			width,
+			height,
+		}
+	}
+	pub const fn with_depth(mut self, depth: &'a [f32]) -> Self {
+		self.depth = Some(depth);
+		self
+	}
+	pub const fn width(&self) -> usize {
+		self.width
+	}
+	pub const fn height(&self) -> usize {
+		self.height
+	}
+	pub fn area(&self) -> BoundingArea2D {
+		BoundingArea2D::new(0, self.width, 0, self.height)
+	}
+	#[inline]
+	pub fn get(&self, x: usize, y: usize) -> T
+	where
+		T: Clone,
+	{
+		self.colour[y * self.width + x].clone()
+	}
+	/// Pixel that is offset from `(x, y)` by `(dx, dy)`, clamped to the edges of the image
+	#[inline]
+	pub fn neighbour(&self, x: usize, y: usize, dx: isize, dy: isize) -> T
+	where
+		T: Clone,
+	{
+		let x = x
+			.saturating_add_signed(dx)
+			.min(self.width.saturating_sub(1));
+		let y = y
+			.saturating_add_signed(dy)
+			.min(self.height.saturating_sub(1));
+		self.get(x, y)
+	}
+	/// Depth of a pixel, or infinity if there is no depth buffer
+	#[inline]
+	pub fn depth(&self, x: usize, y: usize) -> f32 {
+		self.depth
+			.map_or(f32::INFINITY, |depth| depth[y * self.width + x])
+	}
+	/// Depth of the pixel that is offset from `(x, y)` by `(dx, dy)`, clamped to the edges of the image
+	#[inline]
+	pub fn neighbour_depth(&self, x: usize, y: usize, dx: isize, dy: isize) -> f32 {
+		let x = x
+			.saturating_add_signed(dx)
+			.min(self.width.saturating_sub(1));
+		let y = y
+			.saturating_add_signed(dy)
+			.min(self.height.saturating_sub(1));
+		self.depth(x, y)
+	}
+}
+
+/// Full-screen pass, which calculates each pixel of its output from the input buffers
+///
+/// Implemented for closures that take the input and the co-ordinates of the pixel
+pub trait Pass<T> {
+	fn apply(&self, input: &Input<T>, x: usize, y: usize) -> T;
+}
+impl<T, F> Pass<T> for F
+where
+	F: Fn(&Input<T>, usize, usize) -> T,
+{
+	fn apply(&self, input: &Input<T>, x: usize, y: usize) -> T {
+		self(input, x, y)
+	}
+}
+
+/// Pass that changes each pixel on its own, without looking at any other pixels
+pub struct Map<F>(pub F);
+impl<T, F> Pass<T> for Map<F>
+where
+	T: Clone,
+	F: Fn(T) -> T,
+{
+	fn apply(&self, input: &Input<T>, x: usize, y: usize) -> T {
+		(self.0)(input.get(x, y))
+	}
+}
+
+/// Runs a pass, writing its output to `target`
+///
+/// Only pixels that are within both the input and the target's [scissor rectangle](Target::scissor) are written
+pub fn run<T, P, O>(pass: &P, input: &Input<T>, output: &mut O)
+where
+	P: Pass<T>,
+	O: Target<Item = T>,
+{
+	let Some(area) = input.area().intersection(&output.scissor()) else {
+		return;
+	};
+	for y in area.min_y..area.max_y {
+		for x in area.min_x..area.max_x {
+			output.set(x, y, pass.apply(input, x, y));
+		}
+	}
+}
+
+/// Runs a chain of passes over an image
+///
+/// Each pass reads from one buffer and writes to the other, and then the buffers are swapped.
+/// After an odd number of passes the result is held in the scratch buffer, so it should be read with [`PostProcessor::image`]
+pub struct PostProcessor<'a, T> {
+	front: &'a mut [T],
+	back: &'a mut [T],
+	depth: Option<&'a [f32]>,
+	width: usize,
+	height: usize,
+}
+impl<'a, T> PostProcessor<'a, T> {
+	/// Both `image` and `scratch` must hold `width * height` values
+	pub fn new(image: &'a mut [T], scratch: &'a mut [T], width: usize, height: usize) -> Self {
+		Self {
+			front: image,
+			back: scratch,
+			depth: None,
+			width,
+			height,
+		}
+	}
+	/// Adds a depth buffer, which is passed to every pass
+	pub fn with_depth(mut self, depth: &'a [f32]) -> Self {
+		self.depth = Some(depth);
+		self
+	}
+	/// Runs a pass over the output of the previous pass
+	pub fn apply(&mut self, pass: &impl Pass<T>) -> &mut Self {
+		let input = Input {
+			colour: &*self.front,
+			depth: self.depth,
+			width: self.width,
+			height: self.height,
+		};
+		for y in 0..self.height {
+			for x in 0..self.width {
+				self.back[y * self.width + x] = pass.apply(&input, x, y);
+			}
+		}
+		mem::swap(&mut self.front, &mut self.back);
+		self
+	}
+	/// Output of the last pass, as the input to another pass (e.g. to [`run`] the final pass straight into a target)
+	pub fn input(&self) -> Input<'_, T> {
+		Input {
+			colour: self.front,
+			depth: self.depth,
+			width: self.width,
+			height: self.height,
+		}
+	}
+	/// Output of the last pass
+	pub fn image(&self) -> &[T] {
+		self.front
+	}
+	/// Copies the output of the last pass to `target`
+	pub fn copy_to(&self, target: &mut impl Target<Item = T>)
+	where
+		T: Clone,
+	{
+		run(&Map(|value| value), &self.input(), target);
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+	use super::*;
+	use crate::graphics::screen::Screen;
+
+	#[test]
+	fn passes_are_chained() {
+		let (width, height) = (4, 4);
+		let mut image = (0..16).collect::<Vec<u32>>();
+		let mut scratch = vec![0; 16];
+		let depth = vec![0.5; 16];
+		let mut post =
+			PostProcessor::new(&mut image, &mut scratch, width, height).with_depth(&depth);
+		// Shifts the image one pixel to the left, then doubles every pixel
+		let shift = |input: &Input<u32>, x, y| input.neighbour(x, y, 1, 0);
+		post.apply(&shift)
+			.apply(&Map(|value| value * 2))
+			.apply(&|input: &Input<u32>, x, y| {
+				assert_eq!(input.depth(x, y), 0.5);
+				input.get(x, y)
+			});
+		let expected = [1, 2, 3, 3].map(|x| x * 2);
+		assert_eq!(post.image()[..4], expected);
+		assert_eq!(post.image()[12..], expected.map(|x| x + 24));
+
+		let mut frame = vec![0; 16];
+		let mut z_buffer = vec![f32::INFINITY; 16];
+		let mut screen = Screen::new(&mut frame, &mut z_buffer, width, height);
+		post.copy_to(&mut screen);
+		assert_eq!(frame, post.image());
+	}
+}
@@ -0,0 +1,404 @@
+//! Screen-space ambient occlusion (SSAO)
+//!
+//! Darkens creases and corners that ambient light wouldn't reach, using only the depth buffer.
+//! The view-space position of each pixel is reconstructed from its depth and the camera's projection matrix,
+//! and then points in a hemisphere around it are tested against the depth buffer to see how much of it is covered
+use core::f64::consts::TAU;
+
+use rendy3d_maths::{
+	geometry::bounding_area::BoundingArea2D,
+	matrices::{matrix3::MatrixInversionError, matrix4::Matrix4},
+	traits::float::Float,
+	vector::vector3::Vector3,
+};
+
+use crate::graphics::{
+	camera::Camera,
+	colour::Colour,
+	post_process::{Input, Pass, PostProcessor},
+	screen::Screen,
+	target::Target,
+	viewport::DepthRange,
+};
+
+/// Largest number of samples supported by [`Ssao`]
+pub const MAX_SSAO_SAMPLES: usize = 64;
+
+/// Rotation of the sample kernel at each pixel of a 4x4 tile, as a fraction of a full turn out of 16
+///
+/// The pattern repeats every 4 pixels, so the noise it causes is removed by blurring over 4x4 pixels
+const ROTATIONS: [u8; 16] = [0, 8, 2, 10, 12, 4, 14, 6, 3, 11, 1, 9, 15, 7, 13, 5];
+
+/// Settings of the SSAO pass, and the camera it reconstructs positions with
+///
+/// As a [`Pass`], this outputs how visible each pixel is to ambient light (from 0 to 1), using only the depth buffer of the input
+#[derive(Clone, Debug)]
+pub struct Ssao {
+	/// Distance around each pixel that is checked for occluders, in view space units
+	pub radius: f32,
+	/// Distance that an occluder must be in front of a sample by, which stops flat surfaces from occluding themselves
+	pub bias: f32,
+	/// How dark fully occluded pixels are made, from 0 to 1
+	pub strength: f32,
+	/// Offsets of the samples in the hemisphere, where the hemisphere points along z and has a radius of 1
+	kernel: [Vector3<f64>; MAX_SSAO_SAMPLES],
+	samples: usize,
+	projection: Matrix4<f64>,
+	inverse_projection: Matrix4<f64>,
+	depth_range: DepthRange,
+	/// Region of the image that the scene was rendered to, or `None` for the whole image
+	area: Option<BoundingArea2D>,
+}
+impl Ssao {
+	/// Uses the projection matrix that the scene was rendered with, which must be invertible
+	pub fn new(projection: Matrix4<f64>) -> Result<Self, MatrixInversionError> {
+		let inverse_projection = projection.invert()?;
+		Ok(Self {
+			radius: 0.5,
+			bias: 0.025,
+			strength: 1.0,
+			kernel: [Vector3::default(); MAX_SSAO_SAMPLES],
+			samples: 0,
+			projection,
+			inverse_projection,
+			depth_range: DepthRange::default(),
+			area: None,
+		}
+		.with_samples(16))
+	}
+	/// Uses the projection matrix, depth range and viewport area of a camera
+	pub fn from_camera(camera: &Camera) -> Result<Self, MatrixInversionError> {
+		Ok(Self::new(camera.projection.clone())?
+			.with_depth_range(camera.viewport.depth_range)
+			.with_area(camera.viewport.area.clone()))
+	}
+	pub const fn with_radius(mut self, radius: f32) -> Self {
+		self.radius = radius;
+		self
+	}
+	pub const fn with_bias(mut self, bias: f32) -> Self {
+		self.bias = bias;
+		self
+	}
+	pub const fn with_strength(mut self, strength: f32) -> Self {
+		self.strength = strength;
+		self
+	}
+	/// Range that the depth buffer was written with, if it isn't the default
+	pub const fn with_depth_range(mut self, depth_range: DepthRange) -> Self {
+		self.depth_range = depth_range;
+		self
+	}
+	/// Region of the image that the scene was rendered to (e.g. a viewport's area), if it isn't the whole image
+	///
+	/// Pixels outside of the region are left unoccluded
+	pub fn with_area(mut self, area: BoundingArea2D) -> Self {
+		self.area = Some(area);
+		self
+	}
+	/// Region of the image that positions are reconstructed in
+	fn area(&self, input: &Input<f32>) -> BoundingArea2D {
+		match &self.area {
+			Some(area) => area
+				.intersection(&input.area())
+				.unwrap_or(BoundingArea2D::new(0, 0, 0, 0)),
+			None => input.area(),
+		}
+	}
+	/// Number of points tested around each pixel, up to [`MAX_SSAO_SAMPLES`]. More samples give smoother results, but are slower
+	pub fn with_samples(mut self, samples: usize) -> Self {
+		self.samples = samples.clamp(1, MAX_SSAO_SAMPLES);
+		let count = self.samples as f64;
+		for (i, sample) in self.kernel.iter_mut().take(self.samples).enumerate() {
+			// Directions are spread evenly over the hemisphere along a spiral
+			let z = 1.0 - (i as f64 + 0.5) / count;
+			let r = Float::sqrt(1.0 - z * z);
+			let angle = i as f64 * 2.399_963;
+			let direction = Vector3::new(r * Float::cos(angle), r * Float::sin(angle), z);
+			// Distances are scrambled, and weighted towards the centre, where occluders matter most
+			let t = (i as u32).reverse_bits() as f64 / 4_294_967_296.0;
+			*sample = direction * (0.1 + 0.9 * t * t);
+		}
+		self
+	}
+	pub const fn samples(&self) -> usize {
+		self.samples
+	}
+	/// View-space position of the surface at a pixel, or `None` if nothing was drawn there
+	fn position(&self, input: &Input<f32>, x: usize, y: usize) -> Option<Vector3<f64>> {
+		let area = self.area(input);
+		if !(area.min_x..area.max_x).contains(&x) || !(area.min_y..area.max_y).contains(&y) {
+			return None;
+		}
+		let depth = input.depth(x, y);
+		if !depth.is_finite() {
+			return None;
+		}
+		let ndc = Vector3::new(
+			((x - area.min_x) as f64 + 0.5) / area.width() as f64 * 2.0 - 1.0,
+			1.0 - ((y - area.min_y) as f64 + 0.5) / area.height() as f64 * 2.0,
+			self.depth_range.unmap(depth) as f64,
+		);
+		Some(Vector3::from_homogenous(
+			self.inverse_projection.clone() * ndc.to_homogenous(),
+		))
+	}
+	/// Pixel that a view-space position is drawn at, if it is within the rendered region
+	fn project(&self, input: &Input<f32>, position: Vector3<f64>) -> Option<(usize, usize)> {
+		let clip = self.projection.clone() * position.to_homogenous();
+		if clip.w <= 0.0 {
+			return None;
+		}
+		let area = self.area(input);
+		let x = (clip.x / clip.w + 1.0) * 0.5 * area.width() as f64;
+		let y = (1.0 - clip.y / clip.w) * 0.5 * area.height() as f64;
+		let inside =
+			(0.0..area.width() as f64).contains(&x) && (0.0..area.height() as f64).contains(&y);
+		inside.then_some((area.min_x + x as usize, area.min_y + y as usize))
+	}
+	/// Normal of the surface at a pixel, facing the camera
+	///
+	/// On each axis, the neighbour that is closest in depth is used, so that normals aren't distorted at the edges of objects
+	fn normal(
+		&self,
+		input: &Input<f32>,
+		x: usize,
+		y: usize,
+		position: Vector3<f64>,
+	) -> Vector3<f64> {
+		let tangent = |dx: isize, dy: isize| {
+			let mut best: Option<Vector3<f64>> = None;
+			for sign in [1, -1] {
+				let (Some(nx), Some(ny)) = (
+					x.checked_add_signed(dx * sign),
+					y.checked_add_signed(dy * sign),
+				) else {
+					continue;
+				};
+				// Neighbours outside of the rendered region have no position
+				let Some(neighbour) = self.position(input, nx, ny) else {
+					continue;
+				};
+				let offset = (neighbour - position) * sign as f64;
+				if best.is_none_or(|best| offset.z.abs() < best.z.abs()) {
+					best = Some(offset);
+				}
+			}
+			best
+		};
+		let towards_camera = (position * -1.0).normalized();
+		let (Some(horizontal), Some(vertical)) = (tangent(1, 0), tangent(0, 1)) else {
+			return towards_camera;
+		};
+		let normal = horizontal.cross_with(&vertical);
+		if normal.magnitude_squared() < f64::EPSILON {
+			return towards_camera;
+		}
+		let normal = normal.normalized();
+		if normal.dot_with(&towards_camera) < 0.0 {
+			normal * -1.0
+		} else {
+			normal
+		}
+	}
+}
+impl Pass<f32> for Ssao {
+	fn apply(&self, input: &Input<f32>, x: usize, y: usize) -> f32 {
+		let Some(position) = self.position(input, x, y) else {
+			return 1.0;
+		};
+		let normal = self.normal(input, x, y, position);
+		// Basis around the normal, rotated by a different amount at each pixel so that fewer samples are needed
+		let helper = if normal.x.abs() < 0.9 {
+			Vector3::new(1.0, 0.0, 0.0)
+		} else {
+			Vector3::new(0.0, 1.0, 0.0)
+		};
+		let tangent = helper.cross_with(&normal).normalized();
+		let bitangent = normal.cross_with(&tangent);
+		let angle = (ROTATIONS[(y % 4) * 4 + x % 4] as f64 + 0.5) / 16.0 * TAU;
+		let tangent = tangent * Float::cos(angle) + bitangent * Float::sin(angle);
+		let bitangent = normal.cross_with(&tangent);
+
+		let radius = self.radius as f64;
+		let mut occlusion = 0.0;
+		for offset in &self.kernel[..self.samples] {
+			let sample =
+				position + (tangent * offset.x + bitangent * offset.y + normal * offset.z) * radius;
+			let Some(surface) = self
+				.project(input, sample)
+				.and_then(|(x, y)| self.position(input, x, y))
+			else {
+				continue;
+			};
+			// The camera looks along -z, so occluders have a larger z than the sample
+			if surface.z >= sample.z + self.bias as f64 {
+				// Surfaces much further away than the radius (e.g. a distant background) only partly occlude
+				let range = (radius / (position.z - surface.z).abs()).min(1.0);
+				occlusion += range * range * (3.0 - 2.0 * range);
+			}
+		}
+		let occlusion = occlusion / self.samples as f64;
+		1.0 - (occlusion as f32 * self.strength).clamp(0.0, 1.0)
+	}
+}
+
+/// Averages the occlusion over the 4x4 tile that the kernel rotations repeat over
+fn blur(input: &Input<f32>, x: usize, y: usize) -> f32 {
+	let mut total = 0.0;
+	for dy in -2..2 {
+		for dx in -2..2 {
+			total += input.neighbour(x, y, dx, dy);
+		}
+	}
+	total / 16.0
+}
+
+/// Darkens the screen by its ambient occlusion, using its depth buffer
+///
+/// `scratch` must hold twice as many values as the screen has pixels, and is used for the occlusion and its blur
+pub fn ssao(screen: &mut Screen<Colour>, scratch: &mut [f32], settings: &Ssao) {
+	let area = screen.area();
+	if area.is_empty() {
+		return;
+	}
+	let (width, height) = (area.width(), area.height());
+	let (occlusion, blurred) = scratch.split_at_mut(width * height);
+	let mut post = PostProcessor::new(occlusion, &mut blurred[..width * height], width, height)
+		.with_depth(screen.z_buffer);
+	post.apply(settings).apply(&blur);
+	for (pixel, visibility) in screen.frame_buffer.iter_mut().zip(post.image()) {
+		let channel = |c: u8| (c as f32 * visibility + 0.5) as u8;
+		*pixel = Colour::new(
+			channel(pixel.red),
+			channel(pixel.green),
+			channel(pixel.blue),
+			pixel.alpha,
+		);
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+	use rendy3d_maths::geometry::triangle::Triangle;
+
+	use super::*;
+	use crate::{
+		graphics::{
+			geometry::{clipping::GuardBand, point::AbsoluteScreenCoordinate},
+			geometry_3d::point::Point,
+			pipeline::Pipeline,
+			viewport::Viewport,
+		},
+		render::render,
+	};
+
+	/// Projects view-space positions, and draws everything white
+	struct Project;
+	impl Pipeline for Project {
+		type VsOut = ();
+		type VsIn = Matrix4<f64>;
+		type Vertex = Point;
+		type Fragment = Colour;
+		type ClippingStrategy = GuardBand;
+
+		fn vertex(&self, _index: usize, vertex: Point, projection: Matrix4<f64>) -> (Point, ()) {
+			(vertex.apply(projection), ())
+		}
+		fn fragment(&self, _position: AbsoluteScreenCoordinate, _data: ()) -> Colour {
+			Colour::WHITE
+		}
+	}
+
+	/// A wall facing the camera, and a floor that meets it along the bottom of the image.
+	/// The top of the image is left empty
+	fn scene() -> impl Iterator<Item = Triangle<Point>> {
+		let quad = |[a, b, c, d]: [Point; 4]| [Triangle::new(a, b, c), Triangle::new(a, c, d)];
+		let wall = quad([
+			Point::new(-3.0, -1.0, -4.0),
+			Point::new(3.0, -1.0, -4.0),
+			Point::new(3.0, 1.0, -4.0),
+			Point::new(-3.0, 1.0, -4.0),
+		]);
+		let floor = quad([
+			Point::new(-3.0, -1.0, -1.0),
+			Point::new(3.0, -1.0, -1.0),
+			Point::new(3.0, -1.0, -4.0),
+			Point::new(-3.0, -1.0, -4.0),
+		]);
+		wall.into_iter().chain(floor)
+	}
+
+	#[test]
+	fn creases_are_darkened() {
+		let size = 64;
+		let mut frame = vec![Colour::BLACK; size * size];
+		let mut depth = vec![f32::INFINITY; size * size];
+		let mut screen = Screen::new(&mut frame, &mut depth, size, size);
+		let projection = Matrix4::new_perspective(1.2, 1.2, 20.0, 0.1);
+		render(scene(), &mut Project, &mut screen, projection.clone());
+
+		let settings = Ssao::new(projection).unwrap();
+		let mut scratch = vec![0.0; size * size * 2];
+		ssao(&mut screen, &mut scratch, &settings);
+		// Last row of the wall, where it meets the floor, which is closer to the camera
+		let column = |y: usize| depth[y * size + size / 2];
+		let crease = (0..size - 1)
+			.find(|&y| column(y).is_finite() && column(y + 1) < column(y) - 1e-3)
+			.unwrap();
+		let brightness = |x: usize, y: usize| frame[y * size + x].red;
+		// Flat parts of the wall aren't occluded, but the crease is
+		assert!(
+			brightness(32, crease - 8) > 245,
+			"{}",
+			brightness(32, crease - 8)
+		);
+		assert!(brightness(32, crease) < 230, "{}", brightness(32, crease));
+		assert!(brightness(32, crease + 2) < brightness(32, crease + 8));
+		// Nothing was drawn at the top of the image, so it is left alone
+		assert_eq!(frame[size / 2], Colour::BLACK);
+	}
+
+	#[test]
+	fn positions_are_reconstructed_in_the_viewport() {
+		let size = 64;
+		let projection = Matrix4::new_perspective(1.2, 1.2, 20.0, 0.1);
+		let mut frame = vec![Colour::BLACK; size * size];
+		let mut depth = vec![f32::INFINITY; size * size];
+		let mut screen = Screen::new(&mut frame, &mut depth, size, size);
+		render(scene(), &mut Project, &mut screen, projection.clone());
+		let settings = Ssao::new(projection.clone()).unwrap();
+		let mut scratch = vec![0.0; size * size * 2];
+		ssao(&mut screen, &mut scratch, &settings);
+
+		// The same scene, drawn to the right half of an image twice as wide
+		let width = size * 2;
+		let mut wide_frame = vec![Colour::BLACK; width * size];
+		let mut wide_depth = vec![f32::INFINITY; width * size];
+		let mut wide = Screen::new(&mut wide_frame, &mut wide_depth, width, size);
+		let mut viewport = Viewport::new(BoundingArea2D::new(size, width, 0, size)).unwrap();
+		render(
+			scene(),
+			&mut Project,
+			&mut viewport.target(&mut wide),
+			projection.clone(),
+		);
+		let camera = Camera::new(viewport, projection);
+		let settings = Ssao::from_camera(&camera).unwrap();
+		let mut scratch = vec![0.0; width * size * 2];
+		ssao(&mut wide, &mut scratch, &settings);
+		for y in 0..size {
+			// The blur reads a couple of pixels past the left edge of the viewport
+			for x in 2..size {
+				assert_eq!(wide_frame[y * width + size + x], frame[y * size + x]);
+			}
+		}
+	}
+
+	#[test]
+	fn empty_images_are_ignored() {
+		let settings = Ssao::new(Matrix4::new_perspective(1.2, 1.2, 20.0, 0.1)).unwrap();
+		let mut screen = Screen::new(&mut [], &mut [], 0, 0);
+		ssao(&mut screen, &mut [], &settings);
+	}
+}
@@ -0,0 +1,180 @@
+//! Primitives that can be drawn by [`render`](crate::render::render)
+use rendy3d_maths::geometry::triangle::Triangle;
+
+/// Point, line or triangle
+///
+/// [`Triangle`]s can be converted into primitives, so meshes of triangles can be rendered directly
+#[derive(Clone, Debug)]
+pub enum Primitive<V> {
+	Point(V),
+	Line([V; 2]),
+	Triangle(Triangle<V>),
+}
+impl<V> Primitive<V> {
+	pub fn map_vertices<T, U: FnMut(V) -> T>(self, mut map_fn: U) -> Primitive<T> {
+		match self {
+			Primitive::Point(v) => Primitive::Point(map_fn(v)),
+			Primitive::Line(vertices) => Primitive::Line(vertices.map(map_fn)),
+			Primitive::Triangle(triangle) => Primitive::Triangle(triangle.map_vertices(map_fn)),
+		}
+	}
+	/// Iterates over the vertices of the primitive
+	pub fn vertices(&self) -> impl Iterator<Item = &V> {
+		let vertices = match self {
+			Primitive::Point(v) => [Some(v), None, None],
+			Primitive::Line([a, b]) => [Some(a), Some(b), None],
+			Primitive::Triangle(t) => [Some(&t.vertex1), Some(&t.vertex2), Some(&t.vertex3)],
+		};
+		vertices.into_iter().flatten()
+	}
+}
+impl<V> From<Triangle<V>> for Primitive<V> {
+	fn from(triangle: Triangle<V>) -> Self {
+		Primitive::Triangle(triangle)
+	}
+}
+
+/// Draws each vertex as a point
+pub fn point_list<V>(vertices: impl IntoIterator<Item = V>) -> impl Iterator<Item = Primitive<V>> {
+	vertices.into_iter().map(Primitive::Point)
+}
+
+/// Draws a separate line between each pair of vertices
+///
+/// If there are an odd number of vertices, the last one is ignored
+pub fn line_list<V>(vertices: impl IntoIterator<Item = V>) -> impl Iterator<Item = Primitive<V>> {
+	let mut vertices = vertices.into_iter();
+	core::iter::from_fn(move || Some(Primitive::Line([vertices.next()?, vertices.next()?])))
+}
+
+/// Draws a connected line through all of the vertices
+pub fn line_strip<V: Clone>(
+	vertices: impl IntoIterator<Item = V>,
+) -> impl Iterator<Item = Primitive<V>> {
+	let mut vertices = vertices.into_iter();
+	let mut previous = vertices.next();
+	vertices.map(move |v| {
+		let start = previous.replace(v.clone()).unwrap();
+		Primitive::Line([start, v])
+	})
+}
+
+/// How a sequence of vertices is assembled into triangles
+///
+/// Strips and fans keep the winding order of every triangle the same as that of the first one, so [`BackFaceCulling`](crate::graphics::pipeline::back_face_culling::BackFaceCulling) works as expected
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Topology {
+	/// Each group of three vertices is a separate triangle
+	#[default]
+	TriangleList,
+	/// Each vertex makes a triangle with the two vertices before it
+	TriangleStrip,
+	/// Each vertex makes a triangle with the vertex before it and the first vertex
+	TriangleFan,
+}
+
+/// Assembles triangles from a stream of vertices, one vertex at a time
+#[derive(Clone, Debug)]
+pub struct TriangleAssembler<V> {
+	topology: Topology,
+	/// Vertices that will be used by the next triangle
+	pending: [Option<V>; 2],
+	/// Number of vertices since the start of the current primitive
+	count: usize,
+}
+impl<V: Clone> TriangleAssembler<V> {
+	pub fn new(topology: Topology) -> Self {
+		Self {
+			topology,
+			pending: [None, None],
+			count: 0,
+		}
+	}
+	/// Adds a vertex, returning the triangle that it completes (if any)
+	pub fn push(&mut self, vertex: V) -> Option<Triangle<V>> {
+		let count = self.count;
+		self.count += 1;
+		let slot = match self.topology {
+			Topology::TriangleList => count % 3,
+			Topology::TriangleStrip | Topology::TriangleFan => count,
+		};
+		if slot < 2 {
+			self.pending[slot] = Some(vertex);
+			return None;
+		}
+		match self.topology {
+			Topology::TriangleList => {
+				let [a, b] = core::mem::take(&mut self.pending);
+				Some(Triangle::new(a?, b?, vertex))
+			}
+			Topology::TriangleStrip => {
+				let [a, b] = core::mem::take(&mut self.pending);
+				let (a, b) = (a?, b?);
+				self.pending = [Some(b.clone()), Some(vertex.clone())];
+				// Every other triangle in a strip is wound the opposite way, so swap its first two vertices
+				if count.is_multiple_of(2) {
+					Some(Triangle::new(a, b, vertex))
+				} else {
+					Some(Triangle::new(b, a, vertex))
+				}
+			}
+			Topology::TriangleFan => {
+				let b = self.pending[1].replace(vertex.clone())?;
+				Some(Triangle::new(self.pending[0].clone()?, b, vertex))
+			}
+		}
+	}
+	/// Starts a new strip or fan, discarding any incomplete triangle
+	pub fn restart(&mut self) {
+		self.pending = [None, None];
+		self.count = 0;
+	}
+}
+
+/// Assembles `vertices` into triangles, using the given topology
+pub fn triangles<V: Clone>(
+	vertices: impl IntoIterator<Item = V>,
+	topology: Topology,
+) -> impl Iterator<Item = Triangle<V>> {
+	let mut assembler = TriangleAssembler::new(topology);
+	vertices.into_iter().filter_map(move |v| assembler.push(v))
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+	use super::*;
+
+	fn lines(primitives: impl Iterator<Item = Primitive<u8>>) -> Vec<[u8; 2]> {
+		primitives
+			.map(|p| match p {
+				Primitive::Line(line) => line,
+				_ => panic!("Expected a line"),
+			})
+			.collect()
+	}
+
+	#[test]
+	fn line_topologies() {
+		assert_eq!(lines(line_list(0..5)), [[0, 1], [2, 3]]);
+		assert_eq!(lines(line_strip(0..4)), [[0, 1], [1, 2], [2, 3]]);
+		assert!(lines(line_strip(0..1)).is_empty());
+	}
+
+	#[test]
+	fn triangle_topologies() {
+		let assemble = |topology| {
+			triangles(0..6, topology)
+				.map(|t| [t.vertex1, t.vertex2, t.vertex3])
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(assemble(Topology::TriangleList), [[0, 1, 2], [3, 4, 5]]);
+		assert_eq!(
+			assemble(Topology::TriangleStrip),
+			[[0, 1, 2], [2, 1, 3], [2, 3, 4], [4, 3, 5]]
+		);
+		assert_eq!(
+			assemble(Topology::TriangleFan),
+			[[0, 1, 2], [0, 2, 3], [0, 3, 4], [0, 4, 5]]
+		);
+	}
+}
@@ -0,0 +1,356 @@
+//! Triangle rasterizer using incremental edge functions on fixed-point co-ordinates
+use rendy3d_maths::geometry::triangle::Triangle;
+
+use crate::graphics::{
+	geometry::point::{AbsoluteScreenCoordinate, ScreenCoordinate},
+	interpolate::{Interpolate, Weights},
+	multisample::SampleCount,
+	pipeline::{Pipeline, quad::PixelQuad, shade, stats::count, stencil::stencil_test},
+	rasterizer::{Rasterizer, draw_pixel},
+	target::Target,
+};
+
+/// Number of bits of sub-pixel precision used for vertex positions
+pub const SUBPIXEL_BITS: u32 = 8;
+const ONE: i64 = 1 << SUBPIXEL_BITS;
+const HALF: i64 = ONE / 2;
+/// Offset of each pixel in a quad from its top-left pixel
+const QUAD_OFFSETS: [(i64, i64); 4] = [(0, 0), (1, 0), (0, 1), (1, 1)];
+
+/// Vertex position snapped to the sub-pixel grid
+#[derive(Clone, Copy)]
+struct FixedPoint {
+	x: i64,
+	y: i64,
+}
+impl FixedPoint {
+	fn from_screen_coordinate(p: ScreenCoordinate) -> Self {
+		Self {
+			x: (p.x * ONE as f32).round() as i64,
+			y: (p.y * ONE as f32).round() as i64,
+		}
+	}
+}
+
+/// Twice the signed area of a triangle on the sub-pixel grid, which is positive if its vertices are clockwise on the screen
+fn area(v0: FixedPoint, v1: FixedPoint, v2: FixedPoint) -> i64 {
+	(v1.x - v0.x) * (v2.y - v0.y) - (v1.y - v0.y) * (v2.x - v0.x)
+}
+
+/// Signed area of a triangle, as it will be rasterized, for deciding whether it is back-facing
+pub(crate) fn signed_area<U>(triangle: &Triangle<(ScreenCoordinate, U)>) -> i64 {
+	area(
+		FixedPoint::from_screen_coordinate(triangle.vertex1.0),
+		FixedPoint::from_screen_coordinate(triangle.vertex2.0),
+		FixedPoint::from_screen_coordinate(triangle.vertex3.0),
+	)
+}
+
+/// Edge function for the directed edge `a -> b`
+///
+/// The value is positive for points on the interior side of the edge, and is stepped incrementally across the triangle's bounding box
+struct Edge {
+	/// Change in value when moving one pixel in the x direction
+	step_x: i64,
+	/// Change in value when moving one pixel in the y direction
+	step_y: i64,
+	/// Value at the centre of the first pixel of the current row
+	row: i64,
+	/// Subtracted when testing coverage, so that pixel centres lying exactly on an edge are only drawn by one triangle
+	bias: i64,
+}
+impl Edge {
+	fn new(a: FixedPoint, b: FixedPoint, origin: FixedPoint) -> Self {
+		let dx = b.x - a.x;
+		let dy = b.y - a.y;
+		// Top-left fill rule: pixels on an edge are only drawn if it is a top edge (horizontal, with the interior below)
+		// or a left edge (interior to the right)
+		let top_left = (dy == 0 && dx > 0) || dy < 0;
+		Self {
+			step_x: -dy * ONE,
+			step_y: dx * ONE,
+			row: dx * (origin.y - a.y) - dy * (origin.x - a.x),
+			bias: if top_left { 0 } else { 1 },
+		}
+	}
+}
+
+/// Rasterizes triangles with sub-pixel precision, following the top-left fill rule
+///
+/// Triangles that share an edge never leave gaps between them, and pixels along the shared edge are only drawn once.
+/// Pixels are shaded in 2x2 quads using [`Pipeline::fragment_quad`], so that screen-space derivatives are available.
+/// If the target is multisampled, coverage and depth are tested for each sample, but each pixel is still only shaded once
+pub struct EdgeFunctionRasterizer;
+impl<U> Rasterizer<Triangle<(ScreenCoordinate, U)>, U> for EdgeFunctionRasterizer
+where
+	U: Interpolate,
+{
+	#[inline]
+	fn draw<T: Target, P: Pipeline<Fragment = T::Item, VsOut = U>>(
+		target: &mut T,
+		pipeline: &P,
+		item: Triangle<(ScreenCoordinate, U)>,
+	) {
+		let (p0, a0) = &item.vertex1;
+		let (p1, a1) = &item.vertex2;
+		let (p2, a2) = &item.vertex3;
+		let v0 = FixedPoint::from_screen_coordinate(*p0);
+		let v1 = FixedPoint::from_screen_coordinate(*p1);
+		let v2 = FixedPoint::from_screen_coordinate(*p2);
+		// Back faces have already been culled, before the triangle reached the rasterizer
+		let area = area(v0, v1, v2);
+		if area == 0 {
+			return;
+		}
+		// Swap vertices so that the interior is always on the positive side of each edge
+		let ((p1, a1, v1), (p2, a2, v2)) = if area > 0 {
+			((p1, a1, v1), (p2, a2, v2))
+		} else {
+			((p2, a2, v2), (p1, a1, v1))
+		};
+		let inverse_area = 1.0 / area.abs() as f32;
+		let w_reciprocal = [p0.w_reciprocal, p1.w_reciprocal, p2.w_reciprocal];
+
+		// Only visit pixels that are within both the triangle's bounds and the target's scissor rectangle
+		let target_area = target.scissor();
+		let min_x = (v0.x.min(v1.x).min(v2.x) >> SUBPIXEL_BITS).max(target_area.min_x as i64);
+		let min_y = (v0.y.min(v1.y).min(v2.y) >> SUBPIXEL_BITS).max(target_area.min_y as i64);
+		let max_x = ((v0.x.max(v1.x).max(v2.x) >> SUBPIXEL_BITS) + 1).min(target_area.max_x as i64);
+		let max_y = ((v0.y.max(v1.y).max(v2.y) >> SUBPIXEL_BITS) + 1).min(target_area.max_y as i64);
+		if min_x >= max_x || min_y >= max_y {
+			return;
+		}
+
+		// Pixels are shaded in 2x2 quads, aligned to even co-ordinates, so that derivatives can be calculated
+		let start_x = min_x & !1;
+		let start_y = min_y & !1;
+		// Centre of the first pixel visited
+		let origin = FixedPoint {
+			x: (start_x << SUBPIXEL_BITS) + HALF,
+			y: (start_y << SUBPIXEL_BITS) + HALF,
+		};
+		// Each edge function is the weight of the vertex opposite that edge
+		let mut edges = [
+			Edge::new(v1, v2, origin),
+			Edge::new(v2, v0, origin),
+			Edge::new(v0, v1, origin),
+		];
+		let depth = pipeline.depth_state();
+		let stencil = pipeline.stencil_state();
+		let early = pipeline.early_depth_test();
+		// Change in depth when the edge functions change by the given amounts
+		let depth_change = |[s0, s1, s2]: [i64; 3]| {
+			(s0 as f32 * p0.z + s1 as f32 * p1.z + s2 as f32 * p2.z) * inverse_area
+		};
+		// Depth bias is constant across the whole triangle
+		let dz_dx = depth_change(edges.each_ref().map(|e| e.step_x));
+		let dz_dy = depth_change(edges.each_ref().map(|e| e.step_y));
+		let bias = depth.bias.offset(dz_dx, dz_dy);
+		// Change in each edge function (and in depth) from the centre of a pixel to each of its samples
+		let samples = target.sample_count();
+		let mut sample_steps = [([0; 3], 0.0); 8];
+		for (step, (ox, oy)) in sample_steps.iter_mut().zip(samples.offsets()) {
+			let ox = (ox * ONE as f32) as i64;
+			let oy = (oy * ONE as f32) as i64;
+			let dw = edges
+				.each_ref()
+				.map(|e| (e.step_x * ox + e.step_y * oy) >> SUBPIXEL_BITS);
+			let dz = depth_change(dw);
+			*step = (dw, dz);
+		}
+		let sample_steps = &sample_steps[..samples.count()];
+		let mut y = start_y;
+		while y < max_y {
+			let mut w = edges.each_ref().map(|e| e.row);
+			let mut x = start_x;
+			while x < max_x {
+				// Edge function values at each pixel of the quad
+				let mut weights = [[0; 3]; 4];
+				// Samples of each pixel that are covered by the triangle
+				let mut coverage = [0_u8; 4];
+				for (lane, (dx, dy)) in QUAD_OFFSETS.into_iter().enumerate() {
+					let [w0, w1, w2] =
+						[0, 1, 2].map(|i| w[i] + dx * edges[i].step_x + dy * edges[i].step_y);
+					weights[lane] = [w0, w1, w2];
+					if x + dx < min_x || x + dx >= max_x || y + dy < min_y || y + dy >= max_y {
+						continue;
+					}
+					for (sample, ([d0, d1, d2], _)) in sample_steps.iter().enumerate() {
+						// Check if sample is inside triangle
+						// (the sign bit of the combined values is only clear if all of them are non-negative)
+						let inside = ((w0 + d0 - edges[0].bias)
+							| (w1 + d1 - edges[1].bias)
+							| (w2 + d2 - edges[2].bias))
+							>= 0;
+						coverage[lane] |= (inside as u8) << sample;
+					}
+				}
+				if coverage != [0; 4] {
+					let weights = weights
+						.map(|w| Weights::new(w.map(|w| w as f32 * inverse_area), w_reciprocal));
+					for (lane, (dx, dy)) in QUAD_OFFSETS.into_iter().enumerate() {
+						if coverage[lane] == 0 {
+							continue;
+						}
+						// Depth is linear in screen space, so it doesn't need perspective correction
+						let [l0, l1, l2] = weights[lane].screen;
+						// Interpolate Z
+						let z = p0.z * l0 + p1.z * l1 + p2.z * l2 + bias;
+						let p =
+							AbsoluteScreenCoordinate::new((x + dx) as usize, (y + dy) as usize, z);
+						let quad = PixelQuad::new([a0, a1, a2], &weights, lane);
+						if samples == SampleCount::One {
+							draw_pixel(target, pipeline, p, &quad, early);
+							continue;
+						}
+						// Fragment shader only runs once per pixel, even if multiple samples are covered.
+						// Without early depth testing, it runs before the depth test so that discarded fragments can't write depth
+						let late = if early {
+							None
+						} else {
+							match shade(pipeline, p, &quad) {
+								Some(colour) => Some(colour),
+								None => continue,
+							}
+						};
+						// The stencil test is run once for the whole pixel, and passes if any of its samples pass the depth test
+						let covered = coverage[lane];
+						let mut passed = 0_u8;
+						count(pipeline, |s| &s.pixels_tested);
+						stencil_test(target, stencil, p.x, p.y, |target| {
+							for (sample, (_, dz)) in sample_steps.iter().enumerate() {
+								if covered & (1 << sample) == 0 {
+									continue;
+								}
+								let p = AbsoluteScreenCoordinate::new(p.x, p.y, z + dz);
+								if !target.sample_depth_test(p, sample, depth.compare) {
+									continue;
+								}
+								passed |= 1 << sample;
+								if depth.write {
+									target.set_sample_depth(p.x, p.y, sample, p.z);
+								}
+							}
+							passed != 0
+						});
+						if passed == 0 {
+							count(pipeline, |s| &s.pixels_failed);
+							continue;
+						}
+						let Some(colour) = late.or_else(|| shade(pipeline, p, &quad)) else {
+							continue;
+						};
+						for sample in 0..sample_steps.len() {
+							if passed & (1 << sample) == 0 {
+								continue;
+							}
+							let colour = pipeline
+								.blend()
+								.apply(colour.clone(), || target.get_sample(p.x, p.y, sample));
+							target.set_sample(p.x, p.y, sample, colour);
+						}
+						count(pipeline, |s| &s.pixels_written);
+					}
+				}
+				for (w, e) in w.iter_mut().zip(&edges) {
+					*w += 2 * e.step_x;
+				}
+				x += 2;
+			}
+			for e in &mut edges {
+				e.row += 2 * e.step_y;
+			}
+			y += 2;
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use rendy3d_maths::geometry::bounding_area::BoundingArea2D;
+
+	use super::*;
+	use crate::graphics::{geometry::clipping::NoClip, geometry_3d::point::Point, target::Target};
+
+	const SIZE: usize = 64;
+
+	/// Counts how many times each pixel is written to
+	struct CountingTarget {
+		counts: [[u8; SIZE]; SIZE],
+	}
+	impl Target for CountingTarget {
+		type Item = u8;
+		fn set(&mut self, x: usize, y: usize, _value: Self::Item) {
+			self.counts[y][x] += 1;
+		}
+		fn get(&self, x: usize, y: usize) -> Self::Item {
+			self.counts[y][x]
+		}
+		fn set_depth(&mut self, _x: usize, _y: usize, _value: f32) {}
+		fn get_depth(&self, _x: usize, _y: usize) -> f32 {
+			f32::INFINITY
+		}
+		fn clear(&mut self, _fill: Self::Item) {}
+		fn clear_depth(&mut self) {}
+		fn area(&self) -> BoundingArea2D {
+			BoundingArea2D::new(0, SIZE, 0, SIZE)
+		}
+		fn draw_colour(&self) -> Self::Item {
+			0
+		}
+		fn set_draw_colour(&mut self, _v: Self::Item) {}
+	}
+
+	struct Flat;
+	impl Pipeline for Flat {
+		type VsOut = ();
+		type VsIn = ();
+		type Fragment = u8;
+		type Vertex = Point;
+		type ClippingStrategy = NoClip;
+		fn vertex(&self, _index: usize, vertex: Point, _state: ()) -> (Point, ()) {
+			(vertex, ())
+		}
+		fn fragment(&self, _position: AbsoluteScreenCoordinate, _data: ()) -> u8 {
+			0
+		}
+	}
+
+	fn draw(target: &mut CountingTarget, a: (f32, f32), b: (f32, f32), c: (f32, f32)) {
+		let v = |(x, y)| (ScreenCoordinate::new(x, y, 0.0), ());
+		EdgeFunctionRasterizer::draw(target, &Flat, Triangle::new(v(a), v(b), v(c)));
+	}
+
+	#[test]
+	fn shared_edges_are_drawn_once() {
+		let mut target = CountingTarget {
+			counts: [[0; SIZE]; SIZE],
+		};
+		// Fan of triangles around a point, with edges at awkward angles and sub-pixel positions
+		let centre = (31.3, 30.6);
+		let rim = [
+			(2.0, 2.0),
+			(33.5, 0.5),
+			(62.0, 3.25),
+			(63.0, 32.0),
+			(60.1, 61.7),
+			(31.5, 63.0),
+			(1.0, 58.4),
+			(0.0, 31.5),
+		];
+		for i in 0..rim.len() {
+			let next = rim[(i + 1) % rim.len()];
+			// Alternate winding order, which shouldn't make a difference
+			if i % 2 == 0 {
+				draw(&mut target, centre, rim[i], next);
+			} else {
+				draw(&mut target, next, rim[i], centre);
+			}
+		}
+		assert!(target.counts.iter().flatten().all(|c| *c <= 1));
+		// Pixels around the centre are surrounded by triangles, so must all be drawn
+		for row in &target.counts[8..56] {
+			assert!(row[8..56].iter().all(|c| *c == 1));
+		}
+	}
+}
@@ -0,0 +1,60 @@
+//! Rasterizer for lines, with interpolation of vertex shader outputs along the line
+use crate::graphics::{
+	geometry::point::{AbsoluteScreenCoordinate, ScreenCoordinate},
+	interpolate::{Interpolate, Weights},
+	pipeline::{Pipeline, quad::PixelQuad},
+	rasterizer::{Rasterizer, draw_pixel},
+	target::Target,
+};
+
+/// Rasterizes lines one pixel wide, by stepping one pixel at a time along the major axis
+///
+/// The last pixel of each line isn't drawn, so connected lines don't draw the pixels where they meet twice.
+/// Derivatives are calculated from how quickly the position along the line changes across the screen
+pub struct LineRasterizer;
+impl<U> Rasterizer<[(ScreenCoordinate, U); 2], U> for LineRasterizer
+where
+	U: Interpolate,
+{
+	fn draw<T: Target, P: Pipeline<Fragment = T::Item, VsOut = U>>(
+		target: &mut T,
+		pipeline: &P,
+		item: [(ScreenCoordinate, U); 2],
+	) {
+		let [(p0, a0), (p1, a1)] = &item;
+		let dx = p1.x - p0.x;
+		let dy = p1.y - p0.y;
+		let steps = dx.abs().max(dy.abs()).round() as usize;
+		if steps == 0 {
+			return;
+		}
+		// Rate of change of the position along the line in each direction
+		let length_squared = dx * dx + dy * dy;
+		let dt_dx = dx / length_squared;
+		let dt_dy = dy / length_squared;
+		let area = target.scissor();
+		let early = pipeline.early_depth_test();
+		let w_reciprocal = [p0.w_reciprocal, p1.w_reciprocal, p1.w_reciprocal];
+		for i in 0..steps {
+			let t = i as f32 / steps as f32;
+			let x = (p0.x + dx * t).floor();
+			let y = (p0.y + dy * t).floor();
+			if x < area.min_x as f32
+				|| y < area.min_y as f32
+				|| x >= area.max_x as f32
+				|| y >= area.max_y as f32
+			{
+				continue;
+			}
+			let z = p0.z + (p1.z - p0.z) * t;
+			let p = AbsoluteScreenCoordinate::new(x as usize, y as usize, z);
+			// The pixel is treated as the top-left of its quad
+			let weights = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)].map(|(ox, oy)| {
+				let t = t + ox * dt_dx + oy * dt_dy;
+				Weights::new([1.0 - t, t, 0.0], w_reciprocal)
+			});
+			let quad = PixelQuad::new([a0, a1, a1], &weights, 0);
+			draw_pixel(target, pipeline, p, &quad, early);
+		}
+	}
+}
@@ -1,11 +1,61 @@
-use crate::graphics::{pipeline::Pipeline, target::Target};
+use crate::graphics::{
+	geometry::point::AbsoluteScreenCoordinate,
+	pipeline::{Pipeline, quad::PixelQuad, shade, stats::count},
+	target::Target,
+};
 
 pub trait Rasterizer<Input, VsOut> {
 	fn draw<T: Target, P: Pipeline<Fragment = T::Item, VsOut = VsOut>>(
 		target: &mut T,
-		pipeline: &mut P,
+		pipeline: &P,
 		item: Input,
 	);
 }
 
+pub mod edge_function_rasterizer;
+pub mod line_rasterizer;
+pub mod point_rasterizer;
 pub mod triangle_rasterizer;
+
+/// Shades a pixel and draws it to the target
+///
+/// If `early_depth_test` is set (see [`Pipeline::early_depth_test`]), the depth and stencil tests run before the fragment shader, so pixels that are hidden are never shaded
+#[inline]
+pub(crate) fn draw_pixel<T: Target, P: Pipeline<Fragment = T::Item>>(
+	target: &mut T,
+	pipeline: &P,
+	p: AbsoluteScreenCoordinate,
+	quad: &PixelQuad<P::VsOut>,
+	early_depth_test: bool,
+) {
+	if !target.contains_point(p) {
+		return;
+	}
+	let test = |target: &mut T| {
+		count(pipeline, |s| &s.pixels_tested);
+		let passed = p.depth_stencil_test(target, pipeline);
+		if !passed {
+			count(pipeline, |s| &s.pixels_failed);
+		}
+		passed
+	};
+	let fragment = if early_depth_test {
+		if !test(target) {
+			return;
+		}
+		let Some(fragment) = shade(pipeline, p, quad) else {
+			return;
+		};
+		fragment
+	} else {
+		let Some(fragment) = shade(pipeline, p, quad) else {
+			return;
+		};
+		if !test(target) {
+			return;
+		}
+		fragment
+	};
+	p.write_fragment(target, pipeline, fragment);
+	count(pipeline, |s| &s.pixels_written);
+}
@@ -0,0 +1,39 @@
+//! Rasterizer for points
+use crate::graphics::{
+	geometry::point::{AbsoluteScreenCoordinate, ScreenCoordinate},
+	interpolate::{Interpolate, Weights},
+	pipeline::{Pipeline, quad::PixelQuad},
+	rasterizer::{Rasterizer, draw_pixel},
+	target::Target,
+};
+
+/// Rasterizes points by drawing the pixel that contains them
+///
+/// Vertex shader outputs are constant across a point, so all derivatives are zero
+pub struct PointRasterizer;
+impl<U> Rasterizer<(ScreenCoordinate, U), U> for PointRasterizer
+where
+	U: Interpolate,
+{
+	fn draw<T: Target, P: Pipeline<Fragment = T::Item, VsOut = U>>(
+		target: &mut T,
+		pipeline: &P,
+		item: (ScreenCoordinate, U),
+	) {
+		let (p, a) = &item;
+		let area = target.scissor();
+		let x = p.x.floor();
+		let y = p.y.floor();
+		if x < area.min_x as f32
+			|| y < area.min_y as f32
+			|| x >= area.max_x as f32
+			|| y >= area.max_y as f32
+		{
+			return;
+		}
+		let p = AbsoluteScreenCoordinate::new(x as usize, y as usize, p.z);
+		let weights = [Weights::linear([1.0, 0.0, 0.0]); 4];
+		let quad = PixelQuad::new([a, a, a], &weights, 0);
+		draw_pixel(target, pipeline, p, &quad, pipeline.early_depth_test());
+	}
+}
@@ -4,8 +4,8 @@ use rendy3d_maths::{
 
 use crate::graphics::{
 	draw::Draw,
-	geometry::point::AbsoluteScreenCoordinate,
-	interpolate::Interpolate,
+	geometry::point::{AbsoluteScreenCoordinate, ScreenCoordinate},
+	interpolate::{Interpolate, Weights},
 	pipeline::{Pipeline, back_face_culling::BackFaceCulling},
 	rasterizer::Rasterizer,
 	target::Target,
@@ -20,28 +20,31 @@ fn is_between_0_and_1(x: f32) -> bool {
 }
 
 pub struct TriangleRasterizer;
-impl<U> Rasterizer<Triangle<(AbsoluteScreenCoordinate, U)>, U> for TriangleRasterizer
-where
-	U: Interpolate,
-{
+impl TriangleRasterizer {
+	/// Rasterizes a triangle whose vertices have the given reciprocals of their clip-space `w`
 	#[inline]
-	fn draw<T: Target, P: Pipeline<Fragment = T::Item, VsOut = U>>(
+	fn draw_triangle<T, P, U>(
 		target: &mut T,
-		pipeline: &mut P,
+		pipeline: &P,
 		item: Triangle<(AbsoluteScreenCoordinate, U)>,
-	) {
+		w_reciprocal: [f32; 3],
+	) where
+		T: Target,
+		P: Pipeline<Fragment = T::Item, VsOut = U>,
+		U: Interpolate,
+	{
 		let shape = Triangle::new(item.vertex1.0, item.vertex2.0, item.vertex3.0);
-		if !(target.contains_point(shape.vertex1)
-			|| target.contains_point(shape.vertex2)
-			|| target.contains_point(shape.vertex3))
-		{
-			return;
-		}
 		let v0 = absolute_screen_coordinate_to_2d_vec(shape.vertex1);
 		let v1 = absolute_screen_coordinate_to_2d_vec(shape.vertex2);
 		let v2 = absolute_screen_coordinate_to_2d_vec(shape.vertex3);
 		let t2 = Triangle::new(v0, v1, v2);
-		let bounding_area = t2.bounding_area();
+		// Only visit pixels that are within both the triangle's bounds and the target's scissor rectangle
+		let mut bounding_area = t2.bounding_area();
+		bounding_area.max_x += 1;
+		bounding_area.max_y += 1;
+		let Some(bounding_area) = bounding_area.intersection(&target.scissor()) else {
+			return;
+		};
 		let abc = t2.signed_doubled_area();
 		if match P::backface_culling() {
 			BackFaceCulling::CullClockwise => abc >= 0,
@@ -52,9 +55,10 @@ where
 		}
 		let mat = Matrix2::new(v1 - v0, v2 - v0).adjugate();
 		let denom = abc as f32;
+		let early = pipeline.early_depth_test();
 		// Iterate over all pixels that could possibly contain the triangle
-		for y in bounding_area.min_y..=bounding_area.max_y {
-			for x in bounding_area.min_x..=bounding_area.max_x {
+		for y in bounding_area.min_y..bounding_area.max_y {
+			for x in bounding_area.min_x..bounding_area.max_x {
 				let Vector2 { x: l0, y: l1 } = mat * (Vector2::new(x as i32, y as i32) - v0);
 				let l0 = l0 as f32 / denom;
 				let l1 = l1 as f32 / denom;
@@ -65,19 +69,71 @@ where
 					// Interpolate Z
 					let z = shape.vertex1.z * l2 + shape.vertex2.z * l0 + shape.vertex3.z * l1;
 					let p = AbsoluteScreenCoordinate::new(x, y, z);
-					let out = P::VsOut::interpolate3(
+					// Hidden pixels are skipped before running the fragment shader
+					if early
+						&& !(target.contains_point(p) && p.depth_stencil_test(target, pipeline))
+					{
+						continue;
+					}
+					let out = P::VsOut::interpolate_screen(
 						&item.vertex1.1,
 						&item.vertex2.1,
 						&item.vertex3.1,
-						l2,
-						l0,
-						l1,
+						&Weights::new([l2, l0, l1], w_reciprocal),
 					);
 					let colour = pipeline.fragment(p, out);
-					target.set_draw_colour(colour);
-					p.draw(target, pipeline);
+					if pipeline
+						.alpha_test()
+						.is_some_and(|alpha_test| !alpha_test.test(&colour))
+					{
+						continue;
+					}
+					if early {
+						p.write_fragment(target, pipeline, colour);
+					} else {
+						target.set_draw_colour(colour);
+						p.draw(target, pipeline);
+					}
 				}
 			}
 		}
 	}
 }
+/// Interpolates without perspective, as the positions don't have a `w`
+impl<U> Rasterizer<Triangle<(AbsoluteScreenCoordinate, U)>, U> for TriangleRasterizer
+where
+	U: Interpolate,
+{
+	#[inline]
+	fn draw<T: Target, P: Pipeline<Fragment = T::Item, VsOut = U>>(
+		target: &mut T,
+		pipeline: &P,
+		item: Triangle<(AbsoluteScreenCoordinate, U)>,
+	) {
+		Self::draw_triangle(target, pipeline, item, [1.0; 3]);
+	}
+}
+/// Snaps each vertex to the pixel that contains it before rasterizing
+impl<U> Rasterizer<Triangle<(ScreenCoordinate, U)>, U> for TriangleRasterizer
+where
+	U: Interpolate,
+{
+	#[inline]
+	fn draw<T: Target, P: Pipeline<Fragment = T::Item, VsOut = U>>(
+		target: &mut T,
+		pipeline: &P,
+		item: Triangle<(ScreenCoordinate, U)>,
+	) {
+		let w_reciprocal = [
+			item.vertex1.0.w_reciprocal,
+			item.vertex2.0.w_reciprocal,
+			item.vertex3.0.w_reciprocal,
+		];
+		Self::draw_triangle(
+			target,
+			pipeline,
+			item.map_vertices(|(p, a)| (p.to_absolute(), a)),
+			w_reciprocal,
+		);
+	}
+}
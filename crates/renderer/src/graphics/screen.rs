@@ -1,8 +1,10 @@
-use core::{f32, slice};
+use core::{f32, ops::Range, slice};
 
 use rendy3d_maths::geometry::bounding_area::BoundingArea2D;
 
-use crate::graphics::{colour::Colour, target::Target};
+#[cfg(feature = "std")]
+use crate::graphics::target::SplitTarget;
+use crate::graphics::{colour::Colour, multisample::SampleCount, target::Target};
 
 /// Root [`Target`] that actually writes to a framebuffer
 ///
@@ -12,6 +14,7 @@ pub struct Screen<'a, Pixel> {
 	width: usize,
 	height: usize,
 	pub z_buffer: &'a mut [f32],
+	pub stencil_buffer: Option<&'a mut [u8]>,
 	pub draw_colour: Pixel,
 }
 impl<'a, Pixel> Target for Screen<'a, Pixel>
@@ -39,6 +42,7 @@ where
 
 	fn clear(&mut self, fill: Self::Item) {
 		self.clear_depth();
+		self.clear_stencil(0);
 		self.frame_mut().fill(fill);
 	}
 
@@ -61,8 +65,27 @@ where
 	fn set_draw_colour(&mut self, value: Self::Item) {
 		self.draw_colour = value;
 	}
+
+	fn get_stencil(&self, x: usize, y: usize) -> Option<u8> {
+		let i = y * self.width + x;
+		self.stencil_buffer.as_ref().map(|stencil| stencil[i])
+	}
+	fn set_stencil(&mut self, x: usize, y: usize, value: u8) {
+		let i = y * self.width + x;
+		if let Some(stencil) = &mut self.stencil_buffer {
+			stencil[i] = value;
+		}
+	}
+	fn clear_stencil(&mut self, value: u8) {
+		if let Some(stencil) = &mut self.stencil_buffer {
+			stencil.fill(value);
+		}
+	}
 }
-impl<'a, Pixel> Screen<'a, Pixel> where Pixel: Clone + Default {
+impl<'a, Pixel> Screen<'a, Pixel>
+where
+	Pixel: Clone + Default,
+{
 	pub fn new(
 		frame_buffer: &'a mut [Pixel],
 		z_buffer: &'a mut [f32],
@@ -72,11 +95,17 @@ impl<'a, Pixel> Screen<'a, Pixel> where Pixel: Clone + Default {
 		Self {
 			frame_buffer,
 			z_buffer,
+			stencil_buffer: None,
 			width,
 			height,
 			draw_colour: Pixel::default(),
 		}
 	}
+	/// Adds a stencil buffer, which must hold `width * height` values
+	pub fn with_stencil(mut self, stencil_buffer: &'a mut [u8]) -> Self {
+		self.stencil_buffer = Some(stencil_buffer);
+		self
+	}
 	#[inline]
 	pub fn frame(&self) -> &[Pixel] {
 		self.frame_buffer
@@ -90,6 +119,198 @@ impl<'a, Pixel> Screen<'a, Pixel> where Pixel: Clone + Default {
 	}
 }
 
+#[cfg(feature = "std")]
+impl<'a, Pixel> SplitTarget for Screen<'a, Pixel>
+where
+	Pixel: Clone + Default + Send,
+{
+	type Tile<'b>
+		= ScreenTile<'b, Pixel>
+	where
+		Self: 'b;
+
+	fn split(&mut self, area: &BoundingArea2D, rows: usize) -> Vec<Self::Tile<'_>> {
+		let Some(area) = area.intersection(&self.area()) else {
+			return Vec::new();
+		};
+		ScreenTile::split(
+			self.frame_buffer,
+			self.z_buffer,
+			self.stencil_buffer.as_deref_mut(),
+			self.width,
+			SampleCount::One,
+			area,
+			rows,
+			&self.draw_colour,
+		)
+	}
+}
+
+/// Mutable view into a band of rows of a [`Screen`] (or [`MultisampleScreen`](crate::graphics::multisample::MultisampleScreen))
+///
+/// Created by [`SplitTarget::split`]. Co-ordinates are the same as those of the parent [`Screen`]
+pub struct ScreenTile<'a, Pixel> {
+	frame_buffer: &'a mut [Pixel],
+	z_buffer: &'a mut [f32],
+	stencil_buffer: Option<&'a mut [u8]>,
+	width: usize,
+	samples: SampleCount,
+	area: BoundingArea2D,
+	draw_colour: Pixel,
+}
+#[cfg(feature = "std")]
+impl<'a, Pixel> ScreenTile<'a, Pixel>
+where
+	Pixel: Clone,
+{
+	/// Splits the rows of `area` out of buffers holding `samples` samples per pixel (and a stencil buffer with one value per pixel)
+	#[expect(clippy::too_many_arguments)]
+	pub(crate) fn split(
+		frame_buffer: &'a mut [Pixel],
+		z_buffer: &'a mut [f32],
+		stencil_buffer: Option<&'a mut [u8]>,
+		width: usize,
+		samples: SampleCount,
+		area: BoundingArea2D,
+		rows: usize,
+		draw_colour: &Pixel,
+	) -> Vec<Self> {
+		let row_length = width * samples.count();
+		let start = area.min_y * row_length;
+		let end = area.max_y * row_length;
+		let frames = frame_buffer[start..end].chunks_mut(rows * row_length);
+		let depths = z_buffer[start..end].chunks_mut(rows * row_length);
+		let mut stencils = stencil_buffer.map(|stencil| {
+			stencil[area.min_y * width..area.max_y * width].chunks_mut(rows * width)
+		});
+		frames
+			.zip(depths)
+			.enumerate()
+			.map(|(i, (frame_buffer, z_buffer))| {
+				let min_y = area.min_y + i * rows;
+				let max_y = min_y + frame_buffer.len() / row_length;
+				ScreenTile {
+					frame_buffer,
+					z_buffer,
+					stencil_buffer: stencils.as_mut().and_then(Iterator::next),
+					width,
+					samples,
+					area: BoundingArea2D::new(area.min_x, area.max_x, min_y, max_y),
+					draw_colour: draw_colour.clone(),
+				}
+			})
+			.collect()
+	}
+}
+impl<'a, Pixel> ScreenTile<'a, Pixel> {
+	/// Index of the first sample of a pixel
+	#[inline]
+	fn index(&self, x: usize, y: usize) -> usize {
+		((y - self.area.min_y) * self.width + x) * self.samples.count()
+	}
+	/// Indices of every sample of a pixel
+	#[inline]
+	fn samples(&self, x: usize, y: usize) -> Range<usize> {
+		let i = self.index(x, y);
+		i..i + self.samples.count()
+	}
+	#[inline]
+	fn stencil_index(&self, x: usize, y: usize) -> usize {
+		(y - self.area.min_y) * self.width + x
+	}
+}
+impl<'a, Pixel> Target for ScreenTile<'a, Pixel>
+where
+	Pixel: Clone + Default,
+{
+	type Item = Pixel;
+
+	fn set(&mut self, x: usize, y: usize, value: Self::Item) {
+		let samples = self.samples(x, y);
+		self.frame_buffer[samples].fill(value);
+	}
+
+	fn get(&self, x: usize, y: usize) -> Self::Item {
+		self.frame_buffer[self.index(x, y)].clone()
+	}
+
+	fn set_depth(&mut self, x: usize, y: usize, value: f32) {
+		let samples = self.samples(x, y);
+		self.z_buffer[samples].fill(value);
+	}
+
+	fn get_depth(&self, x: usize, y: usize) -> f32 {
+		self.z_buffer[self.index(x, y)]
+	}
+
+	fn clear(&mut self, fill: Self::Item) {
+		self.clear_depth();
+		self.clear_stencil(0);
+		for y in self.area.min_y..self.area.max_y {
+			let start = self.index(self.area.min_x, y);
+			let end = self.index(self.area.max_x, y);
+			self.frame_buffer[start..end].fill(fill.clone());
+		}
+	}
+
+	fn clear_depth(&mut self) {
+		for y in self.area.min_y..self.area.max_y {
+			let start = self.index(self.area.min_x, y);
+			let end = self.index(self.area.max_x, y);
+			self.z_buffer[start..end].fill(f32::INFINITY);
+		}
+	}
+
+	fn area(&self) -> BoundingArea2D {
+		self.area.clone()
+	}
+
+	fn draw_colour(&self) -> Self::Item {
+		self.draw_colour.clone()
+	}
+	fn set_draw_colour(&mut self, value: Self::Item) {
+		self.draw_colour = value;
+	}
+
+	fn get_stencil(&self, x: usize, y: usize) -> Option<u8> {
+		let i = self.stencil_index(x, y);
+		self.stencil_buffer.as_ref().map(|stencil| stencil[i])
+	}
+	fn set_stencil(&mut self, x: usize, y: usize, value: u8) {
+		let i = self.stencil_index(x, y);
+		if let Some(stencil) = &mut self.stencil_buffer {
+			stencil[i] = value;
+		}
+	}
+	fn clear_stencil(&mut self, value: u8) {
+		for y in self.area.min_y..self.area.max_y {
+			let start = self.stencil_index(self.area.min_x, y);
+			let end = self.stencil_index(self.area.max_x, y);
+			if let Some(stencil) = &mut self.stencil_buffer {
+				stencil[start..end].fill(value);
+			}
+		}
+	}
+
+	fn sample_count(&self) -> SampleCount {
+		self.samples
+	}
+	fn set_sample(&mut self, x: usize, y: usize, sample: usize, value: Self::Item) {
+		let i = self.index(x, y) + sample;
+		self.frame_buffer[i] = value;
+	}
+	fn get_sample(&self, x: usize, y: usize, sample: usize) -> Self::Item {
+		self.frame_buffer[self.index(x, y) + sample].clone()
+	}
+	fn set_sample_depth(&mut self, x: usize, y: usize, sample: usize, value: f32) {
+		let i = self.index(x, y) + sample;
+		self.z_buffer[i] = value;
+	}
+	fn get_sample_depth(&self, x: usize, y: usize, sample: usize) -> f32 {
+		self.z_buffer[self.index(x, y) + sample]
+	}
+}
+
 #[inline]
 pub const fn frame_pixels(frame: &mut [u8]) -> &mut [Colour] {
 	// SAFETY: Format for each pixel matches the layout of the `Colour` struct (and is 4 bytes)
@@ -1,6 +1,9 @@
 use rendy3d_maths::geometry::bounding_area::BoundingArea2D;
 
-use crate::graphics::geometry::point::AbsoluteScreenCoordinate;
+use crate::graphics::{
+	geometry::point::AbsoluteScreenCoordinate, multisample::SampleCount,
+	pipeline::depth::DepthCompare, viewport::DepthRange,
+};
 
 /// Target that we can render to
 ///
@@ -20,24 +23,84 @@ pub trait Target {
 	fn draw_point(&mut self, p: AbsoluteScreenCoordinate, colour: Self::Item) {
 		self.set(p.x, p.y, colour);
 	}
+	/// Region of the target that can be drawn to, which defaults to [`Target::area`]
+	///
+	/// Clip space is always mapped onto [`Target::area`], so a smaller scissor rectangle cuts off part of the image rather than resizing it
+	fn scissor(&self) -> BoundingArea2D {
+		self.area()
+	}
+	/// Range of depths that geometry is drawn with. See [`DepthRange`]
+	fn depth_range(&self) -> DepthRange {
+		DepthRange::default()
+	}
+	/// Whether `point` is within the target's [scissor rectangle](Target::scissor)
 	fn contains_point(&self, point: AbsoluteScreenCoordinate) -> bool {
-		let area = &self.area();
+		let area = &self.scissor();
 		point.x >= area.min_x
 			&& point.x < area.max_x
 			&& point.y >= area.min_y
 			&& point.y < area.max_y
 	}
-	fn point_below_z_buffer(&self, p: AbsoluteScreenCoordinate) -> bool {
-		if !self.contains_point(p) {
-			return true;
-		}
-		let (_, _, z) = p.as_tuple();
-		let buffered_z = self.get_depth(p.x, p.y);
-		z > buffered_z && f32::abs(z - buffered_z) >= 0.00001
+	/// Whether `p` is within the target, and passes the depth test against the depth already in the target
+	fn depth_test(&self, p: AbsoluteScreenCoordinate, compare: DepthCompare) -> bool {
+		self.contains_point(p) && compare.test(p.z, self.get_depth(p.x, p.y))
 	}
 	fn set_z_in_z_buffer(&mut self, p: AbsoluteScreenCoordinate) {
 		self.set_depth(p.x, p.y, p.z);
 	}
 	fn draw_colour(&self) -> Self::Item;
 	fn set_draw_colour(&mut self, v: Self::Item);
+
+	// Stencil buffer
+	//
+	// Targets don't have a stencil buffer by default, in which case the stencil test always passes
+
+	/// Value in the stencil buffer, or `None` if the target doesn't have a stencil buffer
+	fn get_stencil(&self, _x: usize, _y: usize) -> Option<u8> {
+		None
+	}
+	fn set_stencil(&mut self, _x: usize, _y: usize, _value: u8) {}
+	fn clear_stencil(&mut self, _value: u8) {}
+
+	// Multisampling
+	//
+	// Targets only store a single sample for each pixel by default, so these just forward to the per-pixel methods
+
+	/// Number of samples stored for each pixel
+	fn sample_count(&self) -> SampleCount {
+		SampleCount::One
+	}
+	fn set_sample(&mut self, x: usize, y: usize, _sample: usize, value: Self::Item) {
+		self.set(x, y, value);
+	}
+	fn get_sample(&self, x: usize, y: usize, _sample: usize) -> Self::Item {
+		self.get(x, y)
+	}
+	fn set_sample_depth(&mut self, x: usize, y: usize, _sample: usize, value: f32) {
+		self.set_depth(x, y, value);
+	}
+	fn get_sample_depth(&self, x: usize, y: usize, _sample: usize) -> f32 {
+		self.get_depth(x, y)
+	}
+	/// Same as [`Target::depth_test`], but tests against the depth of a single sample
+	fn sample_depth_test(
+		&self,
+		p: AbsoluteScreenCoordinate,
+		sample: usize,
+		compare: DepthCompare,
+	) -> bool {
+		self.contains_point(p) && compare.test(p.z, self.get_sample_depth(p.x, p.y, sample))
+	}
+}
+
+/// Target that can be divided into disjoint horizontal tiles, so that each tile can be drawn to from a separate thread
+#[cfg(feature = "std")]
+pub trait SplitTarget: Target {
+	type Tile<'a>: Target<Item = Self::Item> + Send
+	where
+		Self: 'a;
+	/// Splits `area` into tiles containing (at most) `rows` rows of pixels each
+	///
+	/// Tiles use the same co-ordinate system as the target they were split from, and their [`Target::area`] is the region that they cover
+	fn split(&mut self, area: &BoundingArea2D, rows: usize) -> Vec<Self::Tile<'_>>;
 }
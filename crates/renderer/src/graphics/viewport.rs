@@ -1,9 +1,50 @@
 use rendy3d_maths::geometry::bounding_area::BoundingArea2D;
 
-use crate::graphics::target::Target;
+#[cfg(feature = "std")]
+use crate::graphics::target::SplitTarget;
+use crate::graphics::{multisample::SampleCount, target::Target};
 
+/// Region of a target that is drawn to
+///
+/// Clip space is mapped onto `area`, and nothing is drawn outside of it (or outside of the scissor rectangle, if there is one)
 pub struct Viewport {
 	pub area: BoundingArea2D,
+	/// Additional rectangle that drawing is restricted to, without affecting how clip space is mapped onto the target
+	pub scissor: Option<BoundingArea2D>,
+	pub depth_range: DepthRange,
+}
+
+/// Range of depths that a viewport's geometry is drawn with
+///
+/// Depths are in the same units as the depth buffer, where the near and far planes of the view frustum are at -1 and 1.
+/// The default range of -1 to 1 leaves depths unchanged. Narrower ranges can be used to keep some geometry in front of
+/// (or behind) the rest of the scene, e.g. a first-person weapon drawn with a range of -1 to -0.9
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DepthRange {
+	/// Depth of the near plane
+	pub min: f32,
+	/// Depth of the far plane
+	pub max: f32,
+}
+impl DepthRange {
+	pub const fn new(min: f32, max: f32) -> Self {
+		Self { min, max }
+	}
+	/// Maps a depth between -1 and 1 into this range
+	#[inline]
+	pub fn map(&self, z: f32) -> f32 {
+		self.min + (z + 1.0) * 0.5 * (self.max - self.min)
+	}
+	/// Maps a depth in this range back to between -1 and 1
+	#[inline]
+	pub fn unmap(&self, depth: f32) -> f32 {
+		(depth - self.min) / (self.max - self.min) * 2.0 - 1.0
+	}
+}
+impl Default for DepthRange {
+	fn default() -> Self {
+		Self::new(-1.0, 1.0)
+	}
 }
 
 #[derive(Debug)]
@@ -19,18 +60,40 @@ impl Viewport {
 		// if area.max_y > HEIGHT as usize {
 		// 	return Err(ViewportCreationError::MaxYGreaterThanScreenSize);
 		// }
-		Ok(Self { area })
+		Ok(Self {
+			area,
+			scissor: None,
+			depth_range: DepthRange::default(),
+		})
+	}
+	pub fn with_scissor(mut self, scissor: BoundingArea2D) -> Self {
+		self.scissor = Some(scissor);
+		self
+	}
+	pub fn with_depth_range(mut self, depth_range: DepthRange) -> Self {
+		self.depth_range = depth_range;
+		self
 	}
 	pub fn set_area(&mut self, area: BoundingArea2D) {
 		self.area = area;
 	}
 	pub fn target<'a, T: Target>(&'a mut self, parent: &'a mut T) -> ViewportTarget<'a, T> {
-		ViewportTarget::new(parent, &self.area)
+		let target = ViewportTarget::new(parent, &self.area).with_depth_range(self.depth_range);
+		match &self.scissor {
+			Some(scissor) => target.with_scissor(scissor),
+			None => target,
+		}
 	}
 }
 
+/// Target that draws to a region of its parent target
+///
+/// Co-ordinates are the same as in the parent, but anything outside of the viewport's area (and scissor rectangle) is ignored
 pub struct ViewportTarget<'a, T: Target> {
 	area: &'a BoundingArea2D,
+	/// Region of the parent that can be written to
+	scissor: BoundingArea2D,
+	depth_range: DepthRange,
 	parent_target: &'a mut T,
 }
 impl<'a, T> ViewportTarget<'a, T>
@@ -38,11 +101,44 @@ where
 	T: Target,
 {
 	pub fn new(parent: &'a mut T, area: &'a BoundingArea2D) -> Self {
+		let scissor = clamp(area, &parent.scissor());
 		Self {
 			area,
+			scissor,
+			depth_range: DepthRange::default(),
 			parent_target: parent,
 		}
 	}
+	/// Restricts drawing to the part of the viewport that is within `scissor`
+	pub fn with_scissor(mut self, scissor: &BoundingArea2D) -> Self {
+		self.scissor = clamp(&self.scissor, scissor);
+		self
+	}
+	pub fn with_depth_range(mut self, depth_range: DepthRange) -> Self {
+		self.depth_range = depth_range;
+		self
+	}
+	#[inline]
+	fn writable(&self, x: usize, y: usize) -> bool {
+		x >= self.scissor.min_x
+			&& x < self.scissor.max_x
+			&& y >= self.scissor.min_y
+			&& y < self.scissor.max_y
+	}
+	/// Calls `f` for every pixel that can be written to
+	fn for_each_pixel(&mut self, mut f: impl FnMut(&mut T, usize, usize)) {
+		for y in self.scissor.min_y..self.scissor.max_y {
+			for x in self.scissor.min_x..self.scissor.max_x {
+				f(self.parent_target, x, y);
+			}
+		}
+	}
+}
+
+/// Intersection of two areas, which is empty if they don't overlap
+fn clamp(area: &BoundingArea2D, bounds: &BoundingArea2D) -> BoundingArea2D {
+	area.intersection(bounds)
+		.unwrap_or(BoundingArea2D::new(0, 0, 0, 0))
 }
 impl<'a, T> Target for ViewportTarget<'a, T>
 where
@@ -51,7 +147,9 @@ where
 	type Item = T::Item;
 
 	fn set(&mut self, x: usize, y: usize, value: Self::Item) {
-		self.parent_target.set(x, y, value);
+		if self.writable(x, y) {
+			self.parent_target.set(x, y, value);
+		}
 	}
 
 	fn get(&self, x: usize, y: usize) -> Self::Item {
@@ -59,24 +157,37 @@ where
 	}
 
 	fn set_depth(&mut self, x: usize, y: usize, value: f32) {
-		self.parent_target.set_depth(x, y, value);
+		if self.writable(x, y) {
+			self.parent_target.set_depth(x, y, value);
+		}
 	}
 
 	fn get_depth(&self, x: usize, y: usize) -> f32 {
 		self.parent_target.get_depth(x, y)
 	}
 
+	/// Clears the colour, depth and stencil of the pixels in the viewport, leaving the rest of the parent untouched
 	fn clear(&mut self, fill: Self::Item) {
-		self.parent_target.clear(fill);
+		self.for_each_pixel(|target, x, y| {
+			target.set(x, y, fill.clone());
+			target.set_depth(x, y, f32::INFINITY);
+			target.set_stencil(x, y, 0);
+		});
 	}
 
 	fn clear_depth(&mut self) {
-		self.parent_target.clear_depth();
+		self.for_each_pixel(|target, x, y| target.set_depth(x, y, f32::INFINITY));
 	}
 
 	fn area(&self) -> BoundingArea2D {
 		self.area.clone()
 	}
+	fn scissor(&self) -> BoundingArea2D {
+		self.scissor.clone()
+	}
+	fn depth_range(&self) -> DepthRange {
+		self.depth_range
+	}
 
 	fn draw_colour(&self) -> Self::Item {
 		self.parent_target.draw_colour()
@@ -84,4 +195,122 @@ where
 	fn set_draw_colour(&mut self, v: Self::Item) {
 		self.parent_target.set_draw_colour(v);
 	}
+
+	fn get_stencil(&self, x: usize, y: usize) -> Option<u8> {
+		self.parent_target.get_stencil(x, y)
+	}
+	fn set_stencil(&mut self, x: usize, y: usize, value: u8) {
+		if self.writable(x, y) {
+			self.parent_target.set_stencil(x, y, value);
+		}
+	}
+	fn clear_stencil(&mut self, value: u8) {
+		self.for_each_pixel(|target, x, y| target.set_stencil(x, y, value));
+	}
+
+	fn sample_count(&self) -> SampleCount {
+		self.parent_target.sample_count()
+	}
+	fn set_sample(&mut self, x: usize, y: usize, sample: usize, value: Self::Item) {
+		if self.writable(x, y) {
+			self.parent_target.set_sample(x, y, sample, value);
+		}
+	}
+	fn get_sample(&self, x: usize, y: usize, sample: usize) -> Self::Item {
+		self.parent_target.get_sample(x, y, sample)
+	}
+	fn set_sample_depth(&mut self, x: usize, y: usize, sample: usize, value: f32) {
+		if self.writable(x, y) {
+			self.parent_target.set_sample_depth(x, y, sample, value);
+		}
+	}
+	fn get_sample_depth(&self, x: usize, y: usize, sample: usize) -> f32 {
+		self.parent_target.get_sample_depth(x, y, sample)
+	}
+}
+#[cfg(feature = "std")]
+impl<'a, T> SplitTarget for ViewportTarget<'a, T>
+where
+	T: SplitTarget,
+{
+	type Tile<'b>
+		= T::Tile<'b>
+	where
+		Self: 'b;
+
+	fn split(&mut self, area: &BoundingArea2D, rows: usize) -> Vec<Self::Tile<'_>> {
+		match area.intersection(&self.scissor) {
+			Some(area) => self.parent_target.split(&area, rows),
+			None => Vec::new(),
+		}
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+	use rendy3d_maths::geometry::triangle::Triangle;
+
+	use super::*;
+	use crate::{
+		graphics::{
+			colour::Colour,
+			geometry::{clipping::NoClip, point::AbsoluteScreenCoordinate},
+			geometry_3d::point::Point,
+			pipeline::Pipeline,
+			screen::Screen,
+		},
+		render::{render, render_parallel},
+	};
+
+	struct Flat;
+	impl Pipeline for Flat {
+		type VsOut = ();
+		type VsIn = ();
+		type Vertex = Point;
+		type Fragment = Colour;
+		type ClippingStrategy = NoClip;
+
+		fn vertex(&self, _index: usize, vertex: Point, _state: ()) -> (Point, ()) {
+			(vertex, ())
+		}
+		fn fragment(&self, _position: AbsoluteScreenCoordinate, _data: ()) -> Colour {
+			Colour::WHITE
+		}
+	}
+
+	#[test]
+	fn viewports_only_draw_to_their_region() {
+		let (width, height) = (32, 32);
+		let full = Triangle::new(
+			Point::new(-3.0, -3.0, 0.0),
+			Point::new(5.0, -3.0, 0.0),
+			Point::new(-3.0, 5.0, 0.0),
+		);
+		for parallel in [false, true] {
+			let mut frame = vec![Colour::RED; width * height];
+			let mut depth = vec![1.0; width * height];
+			let mut screen = Screen::new(&mut frame, &mut depth, width, height);
+			// Left half of the screen, drawing only to its middle rows
+			let mut viewport = Viewport::new(BoundingArea2D::new(0, 16, 0, 32))
+				.unwrap()
+				.with_scissor(BoundingArea2D::new(0, 32, 8, 24))
+				.with_depth_range(DepthRange::new(0.0, 0.5));
+			let mut target = viewport.target(&mut screen);
+			target.clear(Colour::BLACK);
+			if parallel {
+				render_parallel([full.clone()], &mut Flat, &mut target, ());
+			} else {
+				render([full.clone()], &mut Flat, &mut target, ());
+			}
+			for y in 0..height {
+				for x in 0..width {
+					let inside = x < 16 && (8..24).contains(&y);
+					let expected = if inside { Colour::WHITE } else { Colour::RED };
+					assert_eq!(screen.get(x, y), expected);
+					let expected = if inside { 0.25 } else { 1.0 };
+					assert_eq!(screen.get_depth(x, y), expected);
+				}
+			}
+		}
+	}
 }
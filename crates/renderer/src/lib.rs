@@ -1,4 +1,6 @@
 #![cfg_attr(not(feature = "std"), no_std)]
+// Lets derived code refer to `::rendy3d` from inside this crate too
+extern crate self as rendy3d;
 pub mod graphics;
 pub use rendy3d_maths as maths;
 pub mod render;
@@ -1,79 +1,900 @@
+use rendy3d_maths::geometry::bounding_area::BoundingArea2D;
 use rendy3d_maths::geometry::triangle::Triangle;
-use rendy3d_maths::vector::vector3::Vector3;
 use rendy3d_maths::vector::vector4::Vector4;
 
-use crate::graphics::geometry::clipping::TriangleClipper;
+use crate::graphics::geometry::clipping::{ClippingPlane, TriangleClipper, clip_line};
+use crate::graphics::geometry::point::ScreenCoordinate;
 use crate::graphics::geometry_3d::point::Point;
+use crate::graphics::pipeline::stats::count;
+use crate::graphics::primitive::Primitive;
 use crate::graphics::rasterizer::Rasterizer;
-use crate::graphics::rasterizer::triangle_rasterizer::TriangleRasterizer;
+use crate::graphics::rasterizer::edge_function_rasterizer::{EdgeFunctionRasterizer, signed_area};
+use crate::graphics::rasterizer::line_rasterizer::LineRasterizer;
+use crate::graphics::rasterizer::point_rasterizer::PointRasterizer;
+use crate::graphics::viewport::DepthRange;
+#[cfg(feature = "std")]
+use crate::graphics::{mesh::indexed::IndexedMesh, target::SplitTarget};
 
 use crate::graphics::{interpolate::Interpolate, pipeline::Pipeline, target::Target};
 
-/// Tests a given point for whether it's within the view frustum
+/// Number of rows of pixels in each tile drawn by [`render_parallel`]
+#[cfg(feature = "std")]
+const TILE_ROWS: usize = 16;
+
+/// Maps clip space onto the area and depth range of a target
+struct ViewportTransform {
+	area: BoundingArea2D,
+	depth_range: DepthRange,
+}
+impl ViewportTransform {
+	fn new<T: Target>(target: &T) -> Self {
+		Self {
+			area: target.area(),
+			depth_range: target.depth_range(),
+		}
+	}
+	/// Converts a vertex from clip space to screen space
+	fn to_screen_space<U>(&self, (p, a): (Vector4<f64>, U)) -> (ScreenCoordinate, U) {
+		let mut p = Point::from_clip_space(p).to_screen_coordinate(self.area.clone());
+		p.z = self.depth_range.map(p.z);
+		(p, a)
+	}
+}
+
+/// Runs the vertex and geometry shaders over a primitive, then clips the result and converts it to screen space
+///
+/// The vertices are numbered consecutively, starting from `index`, which is left pointing at the next vertex.
+/// Every primitive that is ready to be rasterized is passed to `emit`
+fn process_primitive<P, U, V, W>(
+	primitive: Primitive<V>,
+	index: &mut usize,
+	pipeline: &P,
+	viewport: &ViewportTransform,
+	state: W,
+	emit: impl FnMut(Primitive<(ScreenCoordinate, U)>),
+) where
+	P: Pipeline<VsOut = U, Vertex = V, VsIn = W>,
+	U: Interpolate + Clone,
+	W: Clone,
+{
+	let shaded = primitive.map_vertices(|vertex| {
+		let vsout = pipeline.vertex(*index, vertex, state.clone());
+		*index += 1;
+		vsout
+	});
+	process_geometry(shaded, pipeline, viewport, emit);
+}
+
+/// Runs the geometry shader over a primitive whose vertices have been shaded, then clips everything it emits and converts it to screen space
+fn process_geometry<P, U>(
+	primitive: Primitive<(Point, U)>,
+	pipeline: &P,
+	viewport: &ViewportTransform,
+	mut emit: impl FnMut(Primitive<(ScreenCoordinate, U)>),
+) where
+	P: Pipeline<VsOut = U>,
+	U: Interpolate + Clone,
+{
+	pipeline.geometry(primitive, |primitive| {
+		// Convert primitive to clip space
+		let clip_space =
+			primitive.map_vertices(|(position, data)| (position.to_clip_space(), data));
+		process_clip_space(clip_space, pipeline, viewport, &mut emit);
+	});
+}
+
+/// Clips a primitive in clip space and converts it to screen space
+fn process_clip_space<P, U>(
+	clip_space: Primitive<(Vector4<f64>, U)>,
+	pipeline: &P,
+	viewport: &ViewportTransform,
+	mut emit: impl FnMut(Primitive<(ScreenCoordinate, U)>),
+) where
+	P: Pipeline<VsOut = U>,
+	U: Interpolate + Clone,
+{
+	match clip_space {
+		Primitive::Triangle(triangle) => process_triangle(triangle, pipeline, viewport, |t| {
+			emit(Primitive::Triangle(t));
+		}),
+		Primitive::Line(line) => {
+			if let Some(line) = clip_line(line) {
+				emit(Primitive::Line(line.map(|v| viewport.to_screen_space(v))));
+			}
+		}
+		Primitive::Point(point) => {
+			// Points are either entirely inside or outside of the view frustum
+			if ClippingPlane::clipping_planes()
+				.into_iter()
+				.all(|plane| plane.contains(point.0))
+			{
+				emit(Primitive::Point(viewport.to_screen_space(point)));
+			}
+		}
+	}
+}
+
+/// Clips a triangle in clip space and converts it to screen space, discarding it if it is back-facing
 ///
-/// Returns `true` if the point is within the view frustum, and `false` if it isn't
-fn test_point(p: Vector4<f64>) -> bool {
-	let (x, y, z, w) = p.as_tuple();
-	(-w <= x && x <= w) && (-w <= y && y <= w) && (-w <= z && z <= w)
+/// Culling happens here, rather than in the rasterizer, so that [`render_parallel`] only culls each triangle once, not once for every tile it overlaps
+fn process_triangle<P, U>(
+	clip_space: Triangle<(Vector4<f64>, U)>,
+	pipeline: &P,
+	viewport: &ViewportTransform,
+	mut emit: impl FnMut(Triangle<(ScreenCoordinate, U)>),
+) where
+	P: Pipeline<VsOut = U>,
+	U: Interpolate + Clone,
+{
+	let vertices = [
+		clip_space.vertex1.0,
+		clip_space.vertex2.0,
+		clip_space.vertex3.0,
+	];
+	count(pipeline, |s| &s.triangles_submitted);
+	// If all vertices are outside of the same plane of the view frustum, discard triangle entirely
+	if ClippingPlane::clipping_planes()
+		.into_iter()
+		.any(|plane| vertices.iter().all(|v| !plane.contains(*v)))
+	{
+		count(pipeline, |s| &s.triangles_rejected);
+		return;
+	}
+	let back_facing =
+		|t: &Triangle<(ScreenCoordinate, U)>| P::backface_culling().culls(signed_area(t));
+	// If the clipping strategy needs to handle any of the vertices, clip
+	if vertices.into_iter().any(P::ClippingStrategy::must_clip) {
+		count(pipeline, |s| &s.triangles_clipped);
+		// Every part of a clipped triangle has the same winding, so the triangle is only counted as culled once
+		let mut culled = false;
+		for t in P::ClippingStrategy::clip(clip_space) {
+			count(pipeline, |s| &s.triangles_from_clipping);
+			let t = t.map_vertices(|v| viewport.to_screen_space(v));
+			if back_facing(&t) {
+				culled = true;
+				continue;
+			}
+			emit(t);
+		}
+		if culled {
+			count(pipeline, |s| &s.triangles_culled);
+		}
+	}
+	// Otherwise, the triangle can be rasterized directly
+	else {
+		// Convert to screen space
+		let t = clip_space.map_vertices(|v| viewport.to_screen_space(v));
+		if back_facing(&t) {
+			count(pipeline, |s| &s.triangles_culled);
+			return;
+		}
+		emit(t);
+	}
 }
 
+/// Rasterizes a primitive in screen space
+#[inline]
+fn draw_primitive<T, P, U>(
+	target: &mut T,
+	pipeline: &P,
+	primitive: Primitive<(ScreenCoordinate, U)>,
+) where
+	T: Target,
+	P: Pipeline<Fragment = T::Item, VsOut = U>,
+	U: Interpolate,
+{
+	match primitive {
+		Primitive::Triangle(t) => EdgeFunctionRasterizer::draw(target, pipeline, t),
+		Primitive::Line(l) => LineRasterizer::draw(target, pipeline, l),
+		Primitive::Point(p) => PointRasterizer::draw(target, pipeline, p),
+	}
+}
+
+/// Renders a mesh of primitives (e.g. [`Triangle`]s) to the target
+///
+/// The vertex shader is passed the position of each vertex in `mesh`.
+/// See [`primitive`](crate::graphics::primitive) for drawing lines and points
 pub fn render<M, P, T, U, V, F, W>(mesh: M, pipeline: &mut P, target: &mut T, state: W)
 where
-	M: IntoIterator<Item = Triangle<V>>,
+	M: IntoIterator<Item: Into<Primitive<V>>>,
 	P: Pipeline<VsOut = U, Fragment = F, Vertex = V, VsIn = W>,
 	T: Target<Item = F>,
 	U: Interpolate + Clone,
 	W: Clone,
 {
-	for triangle in mesh {
-		// Convert triangle to clip space
-		let clip_space = triangle.map_vertices(|vertex| {
-			let vsout = pipeline.vertex(0, vertex, state.clone());
-			// Triangle now in clip space after vertex shader application
-			let clip_space = vsout.0.to_homogenous();
-			(clip_space, vsout.1)
+	let viewport = ViewportTransform::new(target);
+	let mut index = 0;
+	for primitive in mesh {
+		process_primitive(
+			primitive.into(),
+			&mut index,
+			pipeline,
+			&viewport,
+			state.clone(),
+			|p| draw_primitive(target, pipeline, p),
+		);
+	}
+}
+
+/// Input to the vertex shader when drawing with [`render_instanced`]
+#[derive(Clone, Debug)]
+pub struct Instance<S, D> {
+	/// Position of the instance in the slice of instances
+	pub id: usize,
+	/// Data belonging to this instance
+	pub data: D,
+	/// State shared by every instance
+	pub state: S,
+}
+
+/// Renders a copy of the mesh for each element of `instances`
+///
+/// The vertex shader is passed an [`Instance`], holding the instance's id and data alongside `state`.
+/// Instances are drawn one after another, in the order of the slice
+pub fn render_instanced<M, P, T, U, V, F, W, D>(
+	mesh: M,
+	instances: &[D],
+	pipeline: &mut P,
+	target: &mut T,
+	state: W,
+) where
+	M: IntoIterator<Item: Into<Primitive<V>>> + Clone,
+	P: Pipeline<VsOut = U, Fragment = F, Vertex = V, VsIn = Instance<W, D>>,
+	T: Target<Item = F>,
+	U: Interpolate + Clone,
+	W: Clone,
+	D: Clone,
+{
+	let viewport = ViewportTransform::new(target);
+	for (id, data) in instances.iter().enumerate() {
+		let instance = Instance {
+			id,
+			data: data.clone(),
+			state: state.clone(),
+		};
+		// Vertices are numbered from zero in every instance
+		let mut index = 0;
+		for primitive in mesh.clone() {
+			process_primitive(
+				primitive.into(),
+				&mut index,
+				pipeline,
+				&viewport,
+				instance.clone(),
+				|p| draw_primitive(target, pipeline, p),
+			);
+		}
+	}
+}
+
+/// Renders an [`IndexedMesh`] to the target
+///
+/// The vertex shader is passed the index of each vertex in [`IndexedMesh::vertices`], and only runs once for each vertex that is used.
+/// Its output is cached and shared between every triangle that uses the vertex
+#[cfg(feature = "std")]
+pub fn render_indexed<P, T, U, V, F, W, I>(
+	mesh: &IndexedMesh<V, I>,
+	pipeline: &mut P,
+	target: &mut T,
+	state: W,
+) where
+	P: Pipeline<VsOut = U, Fragment = F, Vertex = V, VsIn = W>,
+	T: Target<Item = F>,
+	U: Interpolate + Clone,
+	V: Clone,
+	W: Clone,
+	I: Into<usize> + Copy + PartialEq,
+{
+	let viewport = ViewportTransform::new(target);
+	// Post-transform cache, holding the output of the vertex shader for each vertex in the mesh
+	let mut cache = vec![None; mesh.vertices.len()];
+	for triangle in mesh.triangle_indices() {
+		let shaded = triangle.map_vertices(|i| {
+			cache[i]
+				.get_or_insert_with(|| pipeline.vertex(i, mesh.vertices[i].clone(), state.clone()))
+				.clone()
 		});
-		// Test each vertex for clipping
-		let vertex_test = [
-			test_point(clip_space.vertex1.0),
-			test_point(clip_space.vertex2.0),
-			test_point(clip_space.vertex3.0),
-		];
-		// If all vertices are outside of the view frustum, discard triangle entirely
-		if !(vertex_test[0] || vertex_test[1] || vertex_test[2]) {
+		process_geometry(Primitive::Triangle(shaded), pipeline, &viewport, |p| {
+			draw_primitive(target, pipeline, p)
+		});
+	}
+}
+
+/// Multithreaded version of [`render`]
+///
+/// All primitives are transformed and clipped up-front, and then binned into horizontal tiles of the target.
+/// Each tile is then rasterized on a worker thread. Primitives are drawn in the order they were submitted within each tile,
+/// so the output is identical to that of [`render`]
+#[cfg(feature = "std")]
+pub fn render_parallel<M, P, T, U, V, F, W>(mesh: M, pipeline: &mut P, target: &mut T, state: W)
+where
+	M: IntoIterator<Item: Into<Primitive<V>>>,
+	P: Pipeline<VsOut = U, Fragment = F, Vertex = V, VsIn = W> + Sync,
+	T: SplitTarget<Item = F>,
+	U: Interpolate + Clone + Send + Sync,
+	W: Clone,
+{
+	use crate::graphics::pipeline::stats::WithStats;
+	use std::{num::NonZero, thread};
+
+	let pipeline = &*pipeline;
+	let viewport = ViewportTransform::new(target);
+	let mut primitives = Vec::new();
+	let mut index = 0;
+	for primitive in mesh {
+		process_primitive(
+			primitive.into(),
+			&mut index,
+			pipeline,
+			&viewport,
+			state.clone(),
+			|p| primitives.push(p),
+		);
+	}
+	// Tiles only cover the region that can be drawn to
+	let area = target.scissor();
+	let tiles = target.split(&area, TILE_ROWS);
+	if tiles.is_empty() {
+		return;
+	}
+	// Bin each primitive into every tile that its bounding box overlaps
+	let mut bins = vec![Vec::new(); tiles.len()];
+	for (i, primitive) in primitives.iter().enumerate() {
+		let (min_y, max_y) = primitive
+			.vertices()
+			.fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), (p, _)| {
+				(min.min(p.y), max.max(p.y))
+			});
+		// Widened by a pixel either side, as vertices are snapped to the sub-pixel grid when rasterized
+		let min_y = (min_y - 1.0).floor().max(area.min_y as f32);
+		let max_y = (max_y + 1.0).floor().min((area.max_y - 1) as f32);
+		if min_y > max_y {
 			continue;
 		}
-		// If any vertex is outside the view frustum, clip
-		else if !(vertex_test[0] && vertex_test[1] && vertex_test[2]) {
-			// Clip!
-			for t in P::ClippingStrategy::clip(clip_space) {
-				TriangleRasterizer::draw(
-					target,
-					pipeline,
-					t.map_vertices(|(p, a)| {
-						(
-							Point::from_vector(Vector3::from_homogenous(p))
-								.to_pixel_coordinate(target.area()),
-							a,
-						)
-					}),
-				);
-			}
+		let (min_y, max_y) = (min_y as usize, max_y as usize);
+		let first = (min_y - area.min_y) / TILE_ROWS;
+		let last = (max_y - area.min_y) / TILE_ROWS;
+		for bin in &mut bins[first..=last] {
+			bin.push(i);
 		}
-		// If all vertices are inside the viewing frustum, render
-		else {
-			// Convert to screen space
-			TriangleRasterizer::draw(
-				target,
-				pipeline,
-				clip_space.map_vertices(|(p, a)| {
-					(
-						Point::from_vector(Vector3::from_homogenous(p))
-							.to_pixel_coordinate(target.area()),
-						a,
-					)
-				}),
+	}
+	let workers = thread::available_parallelism()
+		.map_or(1, NonZero::get)
+		.min(tiles.len());
+	// Interleave tiles between workers, so that busy regions of the screen are shared out
+	let mut jobs: Vec<Vec<_>> = (0..workers).map(|_| Vec::new()).collect();
+	for (i, job) in tiles.into_iter().zip(bins).enumerate() {
+		jobs[i % workers].push(job);
+	}
+	let primitives = &primitives;
+	let stats = pipeline.stats();
+	thread::scope(|scope| {
+		for job in jobs {
+			scope.spawn(move || {
+				for (mut tile, bin) in job {
+					let Some(stats) = stats else {
+						for i in bin {
+							draw_primitive(&mut tile, pipeline, primitives[i].clone());
+						}
+						continue;
+					};
+					// Each tile is counted separately, so that threads aren't all updating the same counters
+					let local = WithStats::new(pipeline);
+					for i in bin {
+						draw_primitive(&mut tile, &local, primitives[i].clone());
+					}
+					stats.add(local.stats.get());
+				}
+			});
+		}
+	});
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+	use super::*;
+	use crate::graphics::{
+		colour::Colour,
+		geometry::{
+			clipping::{GuardBand, SutherlandHodgman},
+			point::AbsoluteScreenCoordinate,
+		},
+		geometry_3d::cube::Cube,
+		pipeline::{
+			back_face_culling::BackFaceCulling,
+			stats::{RenderStats, WithStats},
+		},
+		primitive::{line_strip, point_list},
+		screen::Screen,
+		viewport::Viewport,
+	};
+	use core::{
+		cell::{Cell, RefCell},
+		marker::PhantomData,
+	};
+	use rendy3d_maths::{matrices::matrix4::Matrix4, vector::vector3::Vector3};
+
+	struct Rainbow;
+	impl Pipeline for Rainbow {
+		type VsOut = f32;
+		type VsIn = Matrix4<f64>;
+		type Vertex = Point;
+		type Fragment = Colour;
+		type ClippingStrategy = SutherlandHodgman;
+
+		fn vertex(&self, _index: usize, vertex: Point, state: Self::VsIn) -> (Point, f32) {
+			let hue = (vertex.x + vertex.y * 2.0 + vertex.z * 4.0) as f32;
+			(vertex.apply(state), hue)
+		}
+		fn fragment(&self, _position: AbsoluteScreenCoordinate, data: f32) -> Colour {
+			let v = (data * 40.0 + 128.0) as u8;
+			Colour::new(v, 255 - v, v / 2, 255)
+		}
+		fn backface_culling() -> BackFaceCulling {
+			BackFaceCulling::CullClockwise
+		}
+	}
+
+	fn transform(i: usize) -> Matrix4<f64> {
+		let angle = i as f64 * 0.7;
+		Matrix4::new_perspective(1.0, 1.0, 20.0, 0.1)
+			* Matrix4::translation(Vector3::new(0.0, 0.0, -2.0))
+			* Matrix4::rotation_x(angle)
+			* Matrix4::rotation_y(angle * 0.5)
+	}
+
+	#[test]
+	fn parallel_matches_single_threaded() {
+		let (width, height) = (317, 211);
+		let mut frame = [
+			vec![Colour::BLACK; width * height],
+			vec![Colour::BLACK; width * height],
+		];
+		let mut depth = [
+			vec![f32::INFINITY; width * height],
+			vec![f32::INFINITY; width * height],
+		];
+		let [frame_a, frame_b] = &mut frame;
+		let [depth_a, depth_b] = &mut depth;
+		let mut single = Screen::new(frame_a, depth_a, width, height);
+		let mut parallel = Screen::new(frame_b, depth_b, width, height);
+		let mut viewport = Viewport::new(BoundingArea2D::new(13, 290, 7, 200)).unwrap();
+		for i in 0..8 {
+			let cube = Cube::new(1.0 + i as f64 * 0.4);
+			render(
+				cube,
+				&mut Rainbow,
+				&mut viewport.target(&mut single),
+				transform(i),
 			);
+			let cube = Cube::new(1.0 + i as f64 * 0.4);
+			render_parallel(
+				cube,
+				&mut Rainbow,
+				&mut viewport.target(&mut parallel),
+				transform(i),
+			);
+		}
+		assert!(single.frame().iter().any(|c| *c != Colour::BLACK));
+		assert!(single.frame() == parallel.frame());
+		assert!(single.z_buffer == parallel.z_buffer);
+	}
+
+	/// Fills triangles with white, using the clipping strategy `C`
+	struct Fill<C>(PhantomData<C>);
+	impl<C: TriangleClipper<()>> Pipeline for Fill<C> {
+		type VsOut = ();
+		type VsIn = ();
+		type Vertex = Point;
+		type Fragment = Colour;
+		type ClippingStrategy = C;
+
+		fn vertex(&self, _index: usize, vertex: Point, _state: ()) -> (Point, ()) {
+			(vertex, ())
+		}
+		fn fragment(&self, _position: AbsoluteScreenCoordinate, _data: ()) -> Colour {
+			Colour::WHITE
+		}
+	}
+
+	fn fill_screen<C: TriangleClipper<()>>() -> Vec<Colour> {
+		let (width, height) = (64, 48);
+		let mut frame = vec![Colour::BLACK; width * height];
+		let mut depth = vec![f32::INFINITY; width * height];
+		// Every vertex is outside of the view frustum, but the triangle covers the whole screen
+		let triangle = Triangle::new(
+			Point::new(-3.0, -3.0, 0.0),
+			Point::new(5.0, -3.0, 0.0),
+			Point::new(-3.0, 5.0, 0.0),
+		);
+		render(
+			[triangle],
+			&mut Fill::<C>(PhantomData),
+			&mut Screen::new(&mut frame, &mut depth, width, height),
+			(),
+		);
+		frame
+	}
+
+	#[test]
+	fn triangles_covering_screen_are_drawn() {
+		assert!(
+			fill_screen::<SutherlandHodgman>()
+				.iter()
+				.all(|c| *c == Colour::WHITE)
+		);
+		assert!(
+			fill_screen::<GuardBand>()
+				.iter()
+				.all(|c| *c == Colour::WHITE)
+		);
+	}
+
+	/// Overflows the left, right and bottom of the screen, but is within the near and far planes
+	fn overflowing_triangle() -> Triangle<Point> {
+		Triangle::new(
+			Point::new(-3.0, -3.0, 0.0),
+			Point::new(3.0, -3.0, 0.0),
+			Point::new(0.0, 0.5, 0.0),
+		)
+	}
+
+	fn clipping_stats<C: TriangleClipper<()>>() -> RenderStats {
+		let (width, height) = (64, 48);
+		let mut frame = vec![Colour::BLACK; width * height];
+		let mut depth = vec![f32::INFINITY; width * height];
+		let mut pipeline = WithStats::new(Fill::<C>(PhantomData));
+		render(
+			[overflowing_triangle()],
+			&mut pipeline,
+			&mut Screen::new(&mut frame, &mut depth, width, height),
+			(),
+		);
+		pipeline.stats.take()
+	}
+
+	#[test]
+	fn guard_band_only_clips_near_and_far() {
+		let stats = clipping_stats::<GuardBand>();
+		assert_eq!(stats.triangles_clipped, 0);
+		assert_eq!(stats.triangles_from_clipping, 0);
+		assert_ne!(stats.pixels_written, 0);
+		let stats = clipping_stats::<SutherlandHodgman>();
+		assert_eq!(stats.triangles_clipped, 1);
+		assert!(stats.triangles_from_clipping > 1);
+	}
+
+	/// Colours pixels by the x co-ordinate of the vertices
+	struct Gradient;
+	impl Pipeline for Gradient {
+		type VsOut = f32;
+		type VsIn = ();
+		type Vertex = Point;
+		type Fragment = Colour;
+		type ClippingStrategy = SutherlandHodgman;
+
+		fn vertex(&self, _index: usize, vertex: Point, _state: ()) -> (Point, f32) {
+			(vertex, vertex.x as f32)
+		}
+		fn fragment(&self, _position: AbsoluteScreenCoordinate, data: f32) -> Colour {
+			Colour::new(((data + 1.0) * 127.0) as u8, 0, 0, 255)
+		}
+	}
+
+	#[test]
+	fn lines_and_points_are_drawn() {
+		let (width, height) = (32, 32);
+		let mut frame = vec![Colour::BLACK; width * height];
+		let mut depth = vec![f32::INFINITY; width * height];
+		let mut screen = Screen::new(&mut frame, &mut depth, width, height);
+		// Line that extends past both sides of the screen, so must be clipped
+		let line = line_strip([Point::new(-2.0, 0.03, 0.0), Point::new(2.0, 0.03, 0.0)]);
+		render(line, &mut Gradient, &mut screen, ());
+		let row = &screen.frame()[15 * width..16 * width];
+		assert!(row.iter().all(|c| c.alpha == 255));
+		assert!(row.windows(2).all(|c| c[0].red <= c[1].red));
+		assert!(row[0].red < 10 && row[width - 1].red > 245);
+
+		// The second point is beyond the far plane
+		let points = point_list([Point::new(0.5, 0.5, 0.0), Point::new(0.5, 0.5, 2.0)]);
+		render(points, &mut Gradient, &mut screen, ());
+		let drawn: Vec<_> = (0..width * height)
+			.filter(|i| screen.frame()[*i] != Colour::BLACK)
+			.collect();
+		assert_eq!(drawn.len(), width + 1);
+		assert!(drawn.contains(&(8 * width + 24)));
+	}
+
+	/// Counts how many times the fragment shader runs, optionally with early depth testing
+	struct Shaded {
+		early: bool,
+		count: Cell<usize>,
+	}
+	impl Pipeline for Shaded {
+		type VsOut = f32;
+		type VsIn = ();
+		type Vertex = Point;
+		type Fragment = Colour;
+		type ClippingStrategy = SutherlandHodgman;
+
+		fn vertex(&self, _index: usize, vertex: Point, _state: ()) -> (Point, f32) {
+			(vertex, vertex.z as f32)
+		}
+		fn fragment(&self, _position: AbsoluteScreenCoordinate, data: f32) -> Colour {
+			self.count.set(self.count.get() + 1);
+			Colour::new((data * 255.0) as u8, 0, 0, 255)
+		}
+		fn early_depth_test(&self) -> bool {
+			self.early
+		}
+	}
+
+	#[test]
+	fn early_depth_test_skips_hidden_pixels() {
+		let (width, height) = (32, 32);
+		let draw = |early| {
+			let mut frame = vec![Colour::BLACK; width * height];
+			let mut depth = vec![f32::INFINITY; width * height];
+			let mut screen = Screen::new(&mut frame, &mut depth, width, height);
+			let full = |z| {
+				Triangle::new(
+					Point::new(-3.0, -3.0, z),
+					Point::new(5.0, -3.0, z),
+					Point::new(-3.0, 5.0, z),
+				)
+			};
+			let mut pipeline = Shaded {
+				early,
+				count: Cell::new(0),
+			};
+			// The second triangle is completely hidden behind the first
+			render([full(0.2), full(0.8)], &mut pipeline, &mut screen, ());
+			(frame, pipeline.count.get())
+		};
+		let (early_frame, early_count) = draw(true);
+		let (late_frame, late_count) = draw(false);
+		assert_eq!(early_count, width * height);
+		assert_eq!(late_count, 2 * width * height);
+		assert!(early_frame == late_frame);
+	}
+
+	#[test]
+	fn stats_count_each_stage() {
+		let (width, height) = (32, 32);
+		let mut frame = vec![Colour::BLACK; width * height];
+		let mut depth = vec![f32::INFINITY; width * height];
+		let mut screen = Screen::new(&mut frame, &mut depth, width, height);
+		let full = |z| {
+			Triangle::new(
+				Point::new(-3.0, -3.0, z),
+				Point::new(5.0, -3.0, z),
+				Point::new(-3.0, 5.0, z),
+			)
+		};
+		let offscreen = Triangle::new(
+			Point::new(2.0, 0.0, 0.5),
+			Point::new(3.0, 0.0, 0.5),
+			Point::new(2.0, 1.0, 0.5),
+		);
+		let mut pipeline = WithStats::new(Shaded {
+			early: true,
+			count: Cell::new(0),
+		});
+		render(
+			[full(0.2), full(0.8), offscreen],
+			&mut pipeline,
+			&mut screen,
+			(),
+		);
+		let stats = pipeline.stats.take();
+		let pixels = width * height;
+		assert_eq!(stats.triangles_submitted, 3);
+		assert_eq!(stats.triangles_rejected, 1);
+		assert_eq!(stats.triangles_clipped, 2);
+		assert_eq!(stats.pixels_tested, 2 * pixels);
+		assert_eq!(stats.pixels_failed, pixels);
+		assert_eq!(stats.fragments_shaded, pipeline.pipeline.count.get());
+		assert_eq!(stats.pixels_written, pixels);
+		// Taking the statistics resets them
+		assert_eq!(pipeline.stats.get(), RenderStats::default());
+	}
+
+	#[test]
+	fn parallel_stats_match_single_threaded() {
+		let (width, height) = (317, 211);
+		let draw = |parallel: bool| {
+			let mut frame = vec![Colour::BLACK; width * height];
+			let mut depth = vec![f32::INFINITY; width * height];
+			let mut screen = Screen::new(&mut frame, &mut depth, width, height);
+			let mut pipeline = WithStats::new(Rainbow);
+			for i in 0..4 {
+				let cube = Cube::new(1.0 + i as f64 * 0.4);
+				if parallel {
+					render_parallel(cube, &mut pipeline, &mut screen, transform(i));
+				} else {
+					render(cube, &mut pipeline, &mut screen, transform(i));
+				}
+			}
+			pipeline.stats.take()
+		};
+		let (single, parallel) = (draw(false), draw(true));
+		assert_ne!(single.pixels_written, 0);
+		assert_ne!(single.triangles_culled, 0);
+		assert_eq!(single.triangles_submitted, parallel.triangles_submitted);
+		assert_eq!(single.triangles_culled, parallel.triangles_culled);
+		assert_eq!(single.pixels_tested, parallel.pixels_tested);
+		assert_eq!(single.pixels_failed, parallel.pixels_failed);
+		assert_eq!(single.fragments_shaded, parallel.fragments_shaded);
+		assert_eq!(single.pixels_written, parallel.pixels_written);
+	}
+
+	/// Records the index passed to each run of the vertex shader
+	struct Indices(RefCell<Vec<usize>>);
+	impl Pipeline for Indices {
+		type VsOut = f32;
+		type VsIn = ();
+		type Vertex = Point;
+		type Fragment = Colour;
+		type ClippingStrategy = SutherlandHodgman;
+
+		fn vertex(&self, index: usize, vertex: Point, _state: ()) -> (Point, f32) {
+			self.0.borrow_mut().push(index);
+			(vertex, vertex.x as f32)
+		}
+		fn fragment(&self, _position: AbsoluteScreenCoordinate, data: f32) -> Colour {
+			Colour::new(((data + 1.0) * 127.0) as u8, 0, 0, 255)
 		}
 	}
+
+	/// Moves each instance by its offset, and colours it by its id
+	struct Offset;
+	impl Pipeline for Offset {
+		type VsOut = f32;
+		type VsIn = Instance<f64, (f64, f64)>;
+		type Vertex = Point;
+		type Fragment = Colour;
+		type ClippingStrategy = SutherlandHodgman;
+
+		fn vertex(&self, _index: usize, vertex: Point, instance: Self::VsIn) -> (Point, f32) {
+			let (x, y) = instance.data;
+			let scale = instance.state;
+			let position = Point::new(vertex.x * scale + x, vertex.y * scale + y, vertex.z);
+			(position, instance.id as f32)
+		}
+		fn fragment(&self, _position: AbsoluteScreenCoordinate, id: f32) -> Colour {
+			Colour::new(id.round() as u8 * 50, 255, 0, 255)
+		}
+	}
+
+	#[test]
+	fn instances_are_drawn_with_their_data() {
+		let (width, height) = (32, 32);
+		let mut frame = vec![Colour::BLACK; width * height];
+		let mut depth = vec![f32::INFINITY; width * height];
+		let mut screen = Screen::new(&mut frame, &mut depth, width, height);
+		let square = IndexedMesh::new(
+			vec![
+				Point::new(-1.0, -1.0, 0.0),
+				Point::new(1.0, -1.0, 0.0),
+				Point::new(1.0, 1.0, 0.0),
+				Point::new(-1.0, 1.0, 0.0),
+			],
+			vec![0_u16, 1, 2, 0, 2, 3],
+		);
+		let offsets = [(-0.5, -0.5), (0.5, -0.5), (0.0, 0.5)];
+		render_instanced(square.triangles(), &offsets, &mut Offset, &mut screen, 0.25);
+		// Only the three small squares are drawn, each in the colour of its instance
+		let drawn = screen.frame().iter().filter(|c| c.green == 255).count();
+		assert_eq!(drawn, 3 * 8 * 8);
+		for (id, (x, y)) in offsets.into_iter().enumerate() {
+			let x = ((x + 1.0) * width as f64 / 2.0) as usize;
+			let y = ((1.0 - y) * height as f64 / 2.0) as usize;
+			assert_eq!(screen.get(x, y).red, id as u8 * 50);
+		}
+	}
+
+	#[test]
+	fn indexed_vertices_are_shaded_once() {
+		let (width, height) = (32, 32);
+		let mut frame = [
+			vec![Colour::BLACK; width * height],
+			vec![Colour::BLACK; width * height],
+		];
+		let mut depth = [
+			vec![f32::INFINITY; width * height],
+			vec![f32::INFINITY; width * height],
+		];
+		let [frame_a, frame_b] = &mut frame;
+		let [depth_a, depth_b] = &mut depth;
+		// Square made from two triangles that share an edge
+		let square = IndexedMesh::new(
+			vec![
+				Point::new(-0.5, -0.5, 0.0),
+				Point::new(0.5, -0.5, 0.0),
+				Point::new(0.5, 0.5, 0.0),
+				Point::new(-0.5, 0.5, 0.0),
+			],
+			vec![0_u16, 1, 2, 0, 2, 3],
+		);
+		let mut indexed = Indices(RefCell::new(Vec::new()));
+		render_indexed(
+			&square,
+			&mut indexed,
+			&mut Screen::new(frame_a, depth_a, width, height),
+			(),
+		);
+		let mut unindexed = Indices(RefCell::new(Vec::new()));
+		render(
+			square.triangles(),
+			&mut unindexed,
+			&mut Screen::new(frame_b, depth_b, width, height),
+			(),
+		);
+		assert_eq!(*indexed.0.borrow(), [0, 1, 2, 3]);
+		assert_eq!(*unindexed.0.borrow(), [0, 1, 2, 3, 4, 5]);
+		assert!(frame_a.iter().any(|c| *c != Colour::BLACK));
+		assert!(frame_a == frame_b);
+	}
+
+	/// Drops triangles on the left of the screen, and draws a line pointing up from the centre of every other triangle
+	struct Normals;
+	impl Pipeline for Normals {
+		type VsOut = f32;
+		type VsIn = ();
+		type Vertex = Point;
+		type Fragment = Colour;
+		type ClippingStrategy = SutherlandHodgman;
+
+		fn vertex(&self, _index: usize, vertex: Point, _state: ()) -> (Point, f32) {
+			(vertex, 1.0)
+		}
+		fn geometry(
+			&self,
+			primitive: Primitive<(Point, f32)>,
+			mut emit: impl FnMut(Primitive<(Point, f32)>),
+		) {
+			let Primitive::Triangle(triangle) = &primitive else {
+				return emit(primitive);
+			};
+			let vertices = [&triangle.vertex1, &triangle.vertex2, &triangle.vertex3];
+			let [x, y, z] = [|p: &Point| p.x, |p: &Point| p.y, |p: &Point| p.z]
+				.map(|axis| vertices.iter().map(|(p, _)| axis(p)).sum::<f64>() / 3.0);
+			if x < 0.0 {
+				return;
+			}
+			emit(primitive);
+			emit(Primitive::Line([
+				(Point::new(x, y, z), 0.5),
+				(Point::new(x, y + 1.0, z), 0.5),
+			]));
+		}
+		fn fragment(&self, _position: AbsoluteScreenCoordinate, data: f32) -> Colour {
+			Colour::new((data * 255.0) as u8, 0, 0, 255)
+		}
+	}
+
+	#[test]
+	fn geometry_stage_can_drop_and_emit_primitives() {
+		let (width, height) = (32, 32);
+		let mut frame = vec![Colour::BLACK; width * height];
+		let mut depth = vec![f32::INFINITY; width * height];
+		let mut screen = Screen::new(&mut frame, &mut depth, width, height);
+		// Small triangles in the bottom-left and bottom-right corners
+		let left = Triangle::new(
+			Point::new(-1.0, -1.0, 0.5),
+			Point::new(-0.5, -1.0, 0.5),
+			Point::new(-1.0, -0.5, 0.5),
+		);
+		let right = Triangle::new(
+			Point::new(0.5, -1.0, 0.5),
+			Point::new(1.0, -1.0, 0.5),
+			Point::new(0.5, -0.5, 0.5),
+		);
+		render([left, right], &mut Normals, &mut screen, ());
+		assert_eq!(screen.get(6, 30), Colour::BLACK);
+		assert_eq!(screen.get(25, 30).red, 255);
+		// The line starts at the centre of the right triangle, and continues above it
+		assert_eq!(screen.get(26, 20).red, 127);
+		assert_eq!(screen.get(26, 10), Colour::BLACK);
+	}
 }
@@ -10,7 +10,7 @@ use rendy3d::graphics::colour::Colour;
 use rendy3d::graphics::geometry::clipping::SutherlandHodgman;
 use rendy3d::graphics::geometry::point::AbsoluteScreenCoordinate;
 use rendy3d::graphics::geometry_3d::point::Point;
-use rendy3d::graphics::interpolate::{Interpolate, PerspectiveCorrectInterpolate};
+use rendy3d::graphics::interpolate::Interpolate;
 use rendy3d::graphics::mesh::indexed::IndexedMesh;
 use rendy3d::graphics::mesh::vertices::TexturedVertex;
 use rendy3d::graphics::pipeline::Pipeline;
@@ -22,7 +22,7 @@ use rendy3d::maths::geometry::bounding_area::BoundingArea2D;
 use rendy3d::maths::matrices::matrix4::Matrix4;
 use rendy3d::maths::vector::vector2::Vector2;
 use rendy3d::maths::vector::vector3::Vector3;
-use rendy3d::render::render;
+use rendy3d::render::render_indexed;
 use rendy3d_loaders::obj::load_obj_indexed;
 use winit::dpi::LogicalSize;
 use winit::event::{Event, WindowEvent};
@@ -166,9 +166,15 @@ struct Test {
 	light_direction: Vector3<f64>,
 	texture: ImageTexture,
 }
+/// Outputs of the vertex shader, which are interpolated across each triangle
+#[derive(Clone, Copy, Interpolate)]
+struct Varyings {
+	texture_coordinates: Vector2<f64>,
+	intensity: f64,
+}
 impl Pipeline for Test {
 	type VsIn = (Matrix4<f64>, Matrix4<f64>);
-	type VsOut = (PerspectiveCorrectInterpolate<Vector2<f64>>, f64);
+	type VsOut = Varyings;
 	type Vertex = TexturedVertex;
 	type Fragment = Colour;
 	type ClippingStrategy = SutherlandHodgman;
@@ -181,13 +187,12 @@ impl Pipeline for Test {
 		vertex *= state.0;
 		let intensity = vertex.normal.dot_with(&self.light_direction);
 		// dbg!(&vertex);
-		let z = vertex.position.z;
 		(
 			vertex.position.apply(state.1),
-			(
-				PerspectiveCorrectInterpolate::new(vertex.texture, z),
-				intensity / 1.5,
-			),
+			Varyings {
+				texture_coordinates: vertex.texture,
+				intensity: intensity / 1.5,
+			},
 		)
 		// let res = index % 3;
 		// match res {
@@ -199,11 +204,11 @@ impl Pipeline for Test {
 	}
 
 	fn fragment(&self, _pos: AbsoluteScreenCoordinate, data: Self::VsOut) -> Self::Fragment {
-		let texture_coordinates = data.0.get();
+		let texture_coordinates = data.texture_coordinates;
 		let base_colour = self
 			.texture
 			.get_texel(texture_coordinates.x as f32, texture_coordinates.y as f32);
-		let intensity = data.1;
+		let intensity = data.intensity;
 		// let intensity = 1.0;
 		// println!("Fragment");
 		Colour::new(
@@ -222,6 +227,10 @@ impl Pipeline for Test {
 	fn backface_culling() -> rendy3d::graphics::pipeline::back_face_culling::BackFaceCulling {
 		rendy3d::graphics::pipeline::back_face_culling::BackFaceCulling::CullClockwise
 	}
+	// Fragments are never discarded, so hidden pixels don't need to be textured
+	fn early_depth_test(&self) -> bool {
+		true
+	}
 }
 impl World {
 	fn new(cameras: Vec<Camera>, objects: Vec<IndexedMesh<TexturedVertex>>) -> Self {
@@ -278,7 +287,7 @@ impl World {
 					Screen<'_, Colour>,
 				> = &mut camera.viewport.target(screen);
 				let s = (transform, camera.projection.clone());
-				render(object.triangles(), pipeline, target, s);
+				render_indexed(object, pipeline, target, s);
 			}
 		}
 	}
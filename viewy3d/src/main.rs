@@ -4,8 +4,14 @@ use argh::FromArgs;
 use pixels::Error;
 use rendy3d::{
 	graphics::{
-		camera::Camera, colour::Colour, geometry::point::AbsoluteScreenCoordinate, object::Object,
-		screen::Screen, target::Target, viewport::Viewport,
+		camera::Camera,
+		colour::Colour,
+		geometry::point::AbsoluteScreenCoordinate,
+		multisample::{MultisampleScreen, SampleCount},
+		object::Object,
+		screen::Screen,
+		target::Target,
+		viewport::Viewport,
 	},
 	maths::{
 		geometry::bounding_area::BoundingArea2D, matrices::matrix4::Matrix4,
@@ -16,8 +22,9 @@ use rendy3d_loaders::stl::load_stl_indexed;
 use winit::{
 	application::ApplicationHandler,
 	dpi::PhysicalSize,
-	event::WindowEvent,
+	event::{ElementState, KeyEvent, WindowEvent},
 	event_loop::{ActiveEventLoop, EventLoop},
+	keyboard::{KeyCode, PhysicalKey},
 	window::{Window, WindowId},
 };
 use winit_input_helper::WinitInputHelper;
@@ -37,6 +44,18 @@ struct Args {
 	#[argh(positional)]
 	#[expect(dead_code)]
 	file: PathBuf,
+	/// number of samples per pixel used for anti-aliasing (1, 2, 4 or 8)
+	#[argh(option, default = "SampleCount::One", from_str_fn(parse_sample_count))]
+	msaa: SampleCount,
+}
+fn parse_sample_count(value: &str) -> Result<SampleCount, String> {
+	match value {
+		"1" => Ok(SampleCount::One),
+		"2" => Ok(SampleCount::Two),
+		"4" => Ok(SampleCount::Four),
+		"8" => Ok(SampleCount::Eight),
+		_ => Err(format!("unsupported sample count: {value}")),
+	}
 }
 
 struct App {
@@ -46,6 +65,15 @@ struct App {
 	scene: World,
 	font: fontdue::Font,
 	z_buffer: Vec<f32>,
+	/// Stencil buffer, which the overdraw view counts fragments in
+	stencil_buffer: Vec<u8>,
+	samples: SampleCount,
+	/// Colour, depth and stencil samples, if multisampling is enabled
+	sample_buffer: Vec<Colour>,
+	sample_z_buffer: Vec<f32>,
+	sample_stencil_buffer: Vec<u8>,
+	/// Scratch memory for ambient occlusion
+	ssao_buffer: Vec<f32>,
 	surface: Option<softbuffer::Surface<Rc<Window>, Rc<Window>>>,
 }
 impl ApplicationHandler for App {
@@ -79,12 +107,59 @@ impl ApplicationHandler for App {
 				println!("The close button was pressed; stopping");
 				event_loop.exit();
 			}
+			// Cycle through the debug views
+			WindowEvent::KeyboardInput {
+				event:
+					KeyEvent {
+						physical_key: PhysicalKey::Code(KeyCode::Tab),
+						state: ElementState::Pressed,
+						repeat: false,
+						..
+					},
+				..
+			} => {
+				self.scene.debug = self.scene.debug.next();
+			}
+			// Toggle ambient occlusion
+			WindowEvent::KeyboardInput {
+				event:
+					KeyEvent {
+						physical_key: PhysicalKey::Code(KeyCode::KeyO),
+						state: ElementState::Pressed,
+						repeat: false,
+						..
+					},
+				..
+			} => {
+				self.scene.ambient_occlusion = !self.scene.ambient_occlusion;
+			}
+			// Toggle render statistics
+			WindowEvent::KeyboardInput {
+				event:
+					KeyEvent {
+						physical_key: PhysicalKey::Code(KeyCode::KeyI),
+						state: ElementState::Pressed,
+						repeat: false,
+						..
+					},
+				..
+			} => {
+				self.scene.stats = !self.scene.stats;
+			}
 			WindowEvent::Resized(size) => {
 				let width = size.width;
 				let height = size.height;
 				println!("{width}, {height}");
 				// Ensure that Z buffer has enough space allocated
 				self.z_buffer = vec![f32::INFINITY; { width * height } as usize];
+				self.stencil_buffer = vec![0; { width * height } as usize];
+				self.ssao_buffer = vec![0.0; { width * height } as usize * 2];
+				if self.samples != SampleCount::One {
+					let samples = (width * height) as usize * self.samples.count();
+					self.sample_buffer = vec![Colour::BLACK; samples];
+					self.sample_z_buffer = vec![f32::INFINITY; samples];
+					self.sample_stencil_buffer = vec![0; (width * height) as usize];
+				}
 				self.surface
 					.as_mut()
 					.unwrap()
@@ -121,9 +196,27 @@ impl ApplicationHandler for App {
 					&mut self.z_buffer,
 					width as usize,
 					height as usize,
-				);
-				screen.clear(Colour::BLACK);
-				self.scene.draw(&mut screen);
+				)
+				.with_stencil(&mut self.stencil_buffer);
+				let stats = if self.samples == SampleCount::One {
+					screen.clear(Colour::BLACK);
+					self.scene.draw(&mut screen)
+				} else {
+					let mut samples = MultisampleScreen::new(
+						&mut self.sample_buffer,
+						&mut self.sample_z_buffer,
+						width as usize,
+						height as usize,
+						self.samples,
+					)
+					.with_stencil(&mut self.sample_stencil_buffer);
+					samples.clear(Colour::BLACK);
+					let stats = self.scene.draw(&mut samples);
+					samples.resolve(&mut screen);
+					stats
+				};
+				self.scene
+					.apply_ambient_occlusion(&mut screen, &mut self.ssao_buffer);
 				fps_buffer.clear();
 				write!(&mut fps_buffer, "FPS: {fps:.0}").unwrap();
 				draw_text(
@@ -134,6 +227,40 @@ impl ApplicationHandler for App {
 					25.0,
 					width as usize,
 				);
+				let mut lines = match stats {
+					Some(stats) => vec![
+						format!(
+							"Triangles: {} ({} rejected, {} culled)",
+							stats.triangles_submitted,
+							stats.triangles_rejected,
+							stats.triangles_culled
+						),
+						format!(
+							"Fragments: {} shaded, {} written (I to hide)",
+							stats.fragments_shaded, stats.pixels_written
+						),
+					],
+					None => vec![String::from("Statistics: off (I to show)")],
+				};
+				lines.push(format!("View: {:?} (Tab to change)", self.scene.debug));
+				lines.push(format!(
+					"Ambient occlusion: {} (O to toggle)",
+					if self.scene.ambient_occlusion {
+						"on"
+					} else {
+						"off"
+					}
+				));
+				for (i, line) in lines.iter().enumerate() {
+					draw_text(
+						&self.font,
+						AbsoluteScreenCoordinate::new(20, 50 + 20 * i, 0.0),
+						frame_buffer,
+						line,
+						18.0,
+						width as usize,
+					);
+				}
 				buffer.present().unwrap();
 				// Queue a RedrawRequested event.
 				//
@@ -147,7 +274,7 @@ impl ApplicationHandler for App {
 	}
 }
 impl App {
-	pub fn new(scene: World, font: fontdue::Font) -> Self {
+	pub fn new(scene: World, font: fontdue::Font, samples: SampleCount) -> Self {
 		Self {
 			window: None,
 			control: FirstPersonControl::new(0.001),
@@ -155,12 +282,18 @@ impl App {
 			scene,
 			font,
 			z_buffer: Vec::new(),
+			stencil_buffer: Vec::new(),
+			samples,
+			sample_buffer: Vec::new(),
+			sample_z_buffer: Vec::new(),
+			sample_stencil_buffer: Vec::new(),
+			ssao_buffer: Vec::new(),
 			surface: None,
 		}
 	}
 }
 fn main() -> Result<(), Error> {
-	let _args: Args = argh::from_env();
+	let args: Args = argh::from_env();
 	let event_loop = EventLoop::new().unwrap();
 	let _input = WinitInputHelper::new();
 	let viewport = Viewport::new(BoundingArea2D::new(0, 0_usize, 0, 0_usize)).unwrap();
@@ -205,7 +338,7 @@ fn main() -> Result<(), Error> {
 	// 		window.request_redraw();
 	// 	}
 	// });
-	let mut app = App::new(scene, font);
+	let mut app = App::new(scene, font, args.msaa);
 	let res = event_loop.run_app(&mut app);
 	res.map_err(|e| Error::UserDefined(Box::new(e)))
 }
@@ -4,28 +4,73 @@ use rendy3d::{
 	graphics::{
 		camera::Camera,
 		colour::Colour,
-		geometry::{clipping::SutherlandHodgman, point::AbsoluteScreenCoordinate},
+		geometry::{clipping::GuardBand, point::AbsoluteScreenCoordinate},
 		geometry_3d::point::Point,
+		interpolate::Interpolate,
 		mesh::vertices::NormalVertex,
 		object::Object,
-		pipeline::Pipeline,
+		pipeline::{
+			Pipeline,
+			debug::{DebugMode, DebugView},
+			stats::{RenderStats, WithStats},
+		},
+		post_process::ssao::{Ssao, ssao},
 		screen::Screen,
+		target::SplitTarget,
 	},
 	maths::{geometry::triangle::Triangle, matrices::matrix4::Matrix4, vector::vector3::Vector3},
-	render::render,
+	render::render_parallel,
 };
 
 pub struct World {
 	pub cameras: Vec<Camera>,
 	pub objects: Vec<Object>,
+	/// Diagnostic view that the scene is drawn with
+	pub debug: DebugMode,
+	/// Whether creases are darkened with screen-space ambient occlusion
+	pub ambient_occlusion: bool,
+	/// Whether render statistics are gathered while drawing
+	pub stats: bool,
 }
 
 impl World {
 	pub fn new(cameras: Vec<Camera>, objects: Vec<Object>) -> Self {
-		Self { objects, cameras }
+		Self {
+			objects,
+			cameras,
+			debug: DebugMode::Off,
+			ambient_occlusion: false,
+			stats: true,
+		}
+	}
+
+	/// Draws every object from every camera, returning the statistics for the whole frame if they are enabled
+	pub fn draw<T: SplitTarget<Item = Colour>>(&mut self, screen: &mut T) -> Option<RenderStats> {
+		let shaders = CoolShaders {
+			light_direction: Vector3::new(0.0, -0.2, -1.0),
+		};
+		if self.stats {
+			let mut pipeline =
+				DebugView::new(WithStats::new(shaders), self.debug).with_depth_range(0.01, 20.0);
+			self.draw_with(&mut pipeline, screen);
+			Some(pipeline.pipeline.stats.take())
+		} else {
+			let mut pipeline = DebugView::new(shaders, self.debug).with_depth_range(0.01, 20.0);
+			self.draw_with(&mut pipeline, screen);
+			None
+		}
 	}
 
-	pub fn draw(&mut self, screen: &mut Screen<Colour>) {
+	fn draw_with<P, T>(&mut self, pipeline: &mut DebugView<P>, screen: &mut T)
+	where
+		DebugView<P>: Pipeline<
+				Fragment = Colour,
+				Vertex = NormalVertex,
+				VsIn = (Matrix4<f64>, Matrix4<f64>),
+				VsOut: Interpolate + Clone + Send + Sync,
+			> + Sync,
+		T: SplitTarget<Item = Colour>,
+	{
 		let _x: std::time::Duration = SystemTime::now()
 			.duration_since(SystemTime::UNIX_EPOCH)
 			.unwrap();
@@ -36,11 +81,8 @@ impl World {
 
 		for object in &self.objects {
 			for camera in &mut self.cameras {
-				let transform = Matrix4::scale_x(
-					camera.viewport.area.height() as f64 / camera.viewport.area.width() as f64,
-				) * camera.view()
-					* base_transform.clone();
-				render(
+				let transform = aspect_correction(camera) * camera.view() * base_transform.clone();
+				render_parallel(
 					object.mesh.triangles().map(|t| {
 						let a = t.vertex2.position - t.vertex1.position;
 						let b = t.vertex3.position - t.vertex1.position;
@@ -52,12 +94,9 @@ impl World {
 						let v2 = NormalVertex::new(t.vertex3.position, normal);
 						Triangle::new(v0, v1, v2)
 					}),
-					&mut CoolShaders {
-						light_direction: Vector3::new(0.0, -0.2, -1.0),
-					},
+					&mut *pipeline,
 					&mut camera.viewport.target(screen),
-					(transform,
-					camera.projection.clone()),
+					(transform, camera.projection.clone()),
 				);
 				// render_mesh(
 				// 	&mut camera.viewport.target(screen),
@@ -70,8 +109,42 @@ impl World {
 				// );
 			}
 		}
+		// Views that are read back from the depth and stencil buffers are filled in once the whole scene is drawn
+		for camera in &mut self.cameras {
+			pipeline.resolve(&mut camera.viewport.target(screen));
+		}
+	}
+
+	/// Darkens creases in the image drawn from each camera, if ambient occlusion is enabled
+	///
+	/// Debug views are left as they are, so that their colours aren't changed. `scratch` must hold twice as many values as the screen has pixels
+	pub fn apply_ambient_occlusion(&self, screen: &mut Screen<Colour>, scratch: &mut [f32]) {
+		if !self.ambient_occlusion || self.debug != DebugMode::Off {
+			return;
+		}
+		for camera in &self.cameras {
+			if camera.viewport.area.is_empty() {
+				continue;
+			}
+			// The aspect ratio is corrected before projecting, so it is treated as part of the projection
+			let projection = camera.projection.clone() * aspect_correction(camera);
+			let Ok(settings) = Ssao::new(projection) else {
+				continue;
+			};
+			let settings = settings
+				.with_area(camera.viewport.area.clone())
+				.with_depth_range(camera.viewport.depth_range)
+				.with_radius(0.05)
+				.with_bias(0.002);
+			ssao(screen, scratch, &settings);
+		}
 	}
 }
+
+/// Squashes the scene horizontally, so that it isn't stretched to the camera's aspect ratio
+fn aspect_correction(camera: &Camera) -> Matrix4<f64> {
+	Matrix4::scale_x(camera.viewport.area.height() as f64 / camera.viewport.area.width() as f64)
+}
 #[derive(Clone)]
 struct CoolShaders {
 	light_direction: Vector3<f64>,
@@ -81,9 +154,14 @@ impl Pipeline for CoolShaders {
 	type VsOut = Colour;
 	type VsIn = (Matrix4<f64>, Matrix4<f64>);
 	type Vertex = NormalVertex;
-	type ClippingStrategy = SutherlandHodgman;
+	type ClippingStrategy = GuardBand;
 
-	fn vertex(&self, _index: usize, mut vertex: Self::Vertex, state: Self::VsIn) -> (Point, Self::VsOut) {
+	fn vertex(
+		&self,
+		_index: usize,
+		mut vertex: Self::Vertex,
+		state: Self::VsIn,
+	) -> (Point, Self::VsOut) {
 		vertex.position *= state.0;
 		let intensity = vertex.normal.dot_with(&self.light_direction);
 		// println!("{intensity}");
@@ -92,10 +170,13 @@ impl Pipeline for CoolShaders {
 		let c = Colour::new(val, val, val, 0xff);
 		(vertex.position.apply(state.1), c)
 	}
-	fn fragment(&self, pos: AbsoluteScreenCoordinate, data: Self::VsOut) -> Self::Fragment {
+	fn fragment(&self, _pos: AbsoluteScreenCoordinate, data: Self::VsOut) -> Self::Fragment {
 		// let (r, g, b) = hsv_to_rgb((pos.z * 360.0).clamp(0.0, 360.0) as f64 * 0.75, 1.0, 1.0);
 		// Colour::new(r, g, b, 255)
 		data
 		// data
 	}
+	fn early_depth_test(&self) -> bool {
+		true
+	}
 }
@@ -4,7 +4,6 @@ use rendy3d::{
 		colour::Colour,
 		geometry::{clipping::SutherlandHodgman, point::AbsoluteScreenCoordinate},
 		geometry_3d::point::Point,
-		interpolate::PerspectiveCorrectInterpolate,
 		pipeline::Pipeline,
 	},
 	maths::{matrices::matrix4::Matrix4, vector::vector3::Vector3},
@@ -12,7 +11,7 @@ use rendy3d::{
 
 pub struct WebDemo;
 impl Pipeline for WebDemo {
-	type VsOut = PerspectiveCorrectInterpolate<f64>;
+	type VsOut = f64;
 	type VsIn = (f64, f64);
 	type Vertex = Point;
 	type Fragment = Colour;
@@ -30,13 +29,12 @@ impl Pipeline for WebDemo {
 		let vertex = vertex.apply(mat);
 		(
 			vertex.apply(Matrix4::new_perspective(1.0, 1.0, 20.0, 0.1)),
-			PerspectiveCorrectInterpolate::new(360.0 * (1.0 - ((vertex.z + 2.8) / 3.0)), vertex.z),
+			360.0 * (1.0 - ((vertex.z + 2.8) / 3.0)),
 		)
 	}
 
 	fn fragment(&self, _pos: AbsoluteScreenCoordinate, data: Self::VsOut) -> Self::Fragment {
-		let hue = data.get();
-		let (r, g, b) = hsv_to_rgb(hue.clamp(0.0, 360.0), 1.0, 1.0);
+		let (r, g, b) = hsv_to_rgb(data.clamp(0.0, 360.0), 1.0, 1.0);
 		Colour::new(r, g, b, 255)
 	}
 	fn backface_culling() -> rendy3d::graphics::pipeline::back_face_culling::BackFaceCulling {
@@ -3,8 +3,6 @@ use std::time::SystemTime;
 use web_sys::Performance;
 
 /// Standardises getting the current time between wasm and std environments
-
-///
 pub trait Timer {
 	fn secs(&self) -> f64;
 }